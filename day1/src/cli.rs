@@ -0,0 +1,263 @@
+//! Argument parsing and output formatting for the `day1` binary.
+
+use crate::plan::FuelPlan;
+use crate::stream::FuelTotals;
+use std::fmt::Write;
+
+pub const USAGE: &str = "\
+Usage: day1 <naive|recursive> [path] [--per-module] [--json] [--threads <n>]
+
+Prints the total fuel for the masses in `path`, or on standard input when no
+path is given. Masses may be separated by whitespace, commas or newlines, and
+`#` starts a comment. Totals are computed while the input is read, unless the
+fuel of every module is listed.
+
+  naive          fuel for the modules alone
+  recursive      fuel for the modules and for the fuel itself
+  --per-module   list the fuel of every module as well
+  --json         print JSON instead of text
+  --threads <n>  split the input across `n` threads";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Total {
+    Naive,
+    Recursive,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub total: Total,
+    pub path: Option<String>,
+    pub per_module: bool,
+    pub json: bool,
+    pub threads: usize,
+}
+
+/// What the command line asks for.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Help,
+    Run(Options),
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut total = None;
+    let mut path = None;
+    let mut per_module = false;
+    let mut json = false;
+    let mut threads = 1;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--per-module" => per_module = true,
+            "--json" => json = true,
+            "--threads" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| String::from("Missing value for `--threads`."))?;
+                threads = match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(format!("Invalid thread count `{}`.", value)),
+                };
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option `{}`.", flag)),
+            "naive" if total.is_none() => total = Some(Total::Naive),
+            "recursive" if total.is_none() => total = Some(Total::Recursive),
+            _ if total.is_none() => return Err(format!("Unknown command `{}`.", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("Unexpected argument `{}`.", arg)),
+        }
+    }
+    let total = total.ok_or_else(|| String::from("Missing command."))?;
+    if per_module && threads > 1 {
+        return Err(String::from(
+            "`--threads` cannot be combined with `--per-module`.",
+        ));
+    }
+    Ok(Command::Run(Options {
+        total,
+        path,
+        per_module,
+        json,
+        threads,
+    }))
+}
+
+/// Formats the requested total of `totals`, for output without modules.
+pub fn render_totals(totals: &FuelTotals, options: &Options) -> String {
+    let total = match options.total {
+        Total::Naive => totals.naive,
+        Total::Recursive => totals.recursive,
+    };
+    if options.json {
+        format!("{{\"total\":{}}}\n", total)
+    } else {
+        format!("{}\n", total)
+    }
+}
+
+/// Formats the requested totals. Fails if the requested total overflows.
+pub fn render(plan: &FuelPlan, options: &Options) -> Result<String, String> {
+    let total = match options.total {
+        Total::Naive => plan.naive_total()?,
+        Total::Recursive => plan.recursive_total()?,
+    };
+    let mut out = String::new();
+    if options.json {
+        write!(out, "{{\"total\":{}", total).unwrap();
+        if options.per_module {
+            let modules: Vec<String> = plan
+                .modules
+                .iter()
+                .map(|module| {
+                    format!(
+                        "{{\"mass\":{},\"naive\":{},\"recursive\":{},\"steps\":{}}}",
+                        module.mass, module.naive, module.recursive, module.steps
+                    )
+                })
+                .collect();
+            write!(out, ",\"modules\":[{}]", modules.join(",")).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    } else if options.per_module {
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>12} {:>6}",
+            "mass", "naive", "recursive", "steps"
+        )
+        .unwrap();
+        for module in &plan.modules {
+            writeln!(
+                out,
+                "{:>12} {:>12} {:>12} {:>6}",
+                module.mass, module.naive, module.recursive, module.steps
+            )
+            .unwrap();
+        }
+        // Only the requested total has to fit; the other one may overflow.
+        let cell = |total: Result<i64, String>| match total {
+            Ok(total) => total.to_string(),
+            Err(_) => String::from("overflow"),
+        };
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>12} {:>6}",
+            "total",
+            cell(plan.naive_total()),
+            cell(plan.recursive_total()),
+            plan.total_steps()
+        )
+        .unwrap();
+    } else {
+        writeln!(out, "{}", total).unwrap();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Command, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    fn options(line: &str) -> Options {
+        match args(line).unwrap() {
+            Command::Run(options) => options,
+            Command::Help => panic!("unexpected help"),
+        }
+    }
+
+    #[test]
+    fn parses_commands_and_flags() {
+        assert_eq!(
+            options("recursive input --json"),
+            Options {
+                total: Total::Recursive,
+                path: Some(String::from("input")),
+                per_module: false,
+                json: true,
+                threads: 1,
+            }
+        );
+        let naive = options("--per-module naive");
+        assert_eq!(
+            (naive.total, naive.path, naive.per_module),
+            (Total::Naive, None, true)
+        );
+        assert_eq!(args("naive --help"), Ok(Command::Help));
+        assert_eq!(options("naive --threads 8").threads, 8);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(args(""), Err(String::from("Missing command.")));
+        assert_eq!(args("total"), Err(String::from("Unknown command `total`.")));
+        assert_eq!(
+            args("naive --csv"),
+            Err(String::from("Unknown option `--csv`."))
+        );
+        assert_eq!(
+            args("naive a b"),
+            Err(String::from("Unexpected argument `b`."))
+        );
+        assert_eq!(
+            args("naive --threads"),
+            Err(String::from("Missing value for `--threads`."))
+        );
+        assert_eq!(
+            args("naive --threads 0"),
+            Err(String::from("Invalid thread count `0`."))
+        );
+        assert!(args("naive --per-module --threads 2").is_err());
+    }
+
+    #[test]
+    fn renders_text_and_json() {
+        let plan = FuelPlan::new(vec![14, 1969]);
+        assert_eq!(render(&plan, &options("recursive")).unwrap(), "968\n");
+        assert_eq!(
+            render(&plan, &options("naive --per-module")).unwrap(),
+            "        mass        naive    recursive  steps\n\
+             \x20         14            2            2      1\n\
+             \x20       1969          654          966      5\n\
+             \x20      total          656          968      6\n"
+        );
+        assert_eq!(
+            render(&plan, &options("naive --json --per-module")).unwrap(),
+            "{\"total\":656,\"modules\":[\
+             {\"mass\":14,\"naive\":2,\"recursive\":2,\"steps\":1},\
+             {\"mass\":1969,\"naive\":654,\"recursive\":966,\"steps\":5}]}\n"
+        );
+        let totals = FuelTotals {
+            modules: 2,
+            naive: 656,
+            recursive: 968,
+        };
+        assert_eq!(render_totals(&totals, &options("naive")), "656\n");
+        assert_eq!(
+            render_totals(&totals, &options("recursive --json")),
+            "{\"total\":968}\n"
+        );
+        let heavy = FuelPlan::new(vec![i64::MAX / 2; 5]);
+        assert!(render(&heavy, &options("recursive")).is_err());
+    }
+
+    #[test]
+    fn only_the_requested_total_has_to_fit() {
+        // The naive total fits, but the recursive one does not.
+        let heavy = FuelPlan::new(vec![i64::MAX / 2; 5]);
+        let naive = heavy.naive_total().unwrap();
+        let table = render(&heavy, &options("naive --per-module")).unwrap();
+        assert!(table.ends_with(&format!(
+            "{:>12} {:>12} {:>12} {:>6}\n",
+            "total",
+            naive,
+            "overflow",
+            heavy.total_steps()
+        )));
+        assert!(render(&heavy, &options("recursive --per-module")).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Configurable fuel formulas, for rocket stages with different efficiencies.
+//!
+//! A formula turns an amount of mass into the fuel it needs. The recursive fuel
+//! adds the fuel for that fuel and so on, for as long as the extra fuel is above
+//! the formula's threshold. All arithmetic is checked, and overflows are
+//! reported as errors.
+
+use crate::plan::ModuleFuel;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+/// How `mass / divisor` is rounded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// Like integer division in Rust.
+    TowardZero,
+    Down,
+    Up,
+    /// Halves round away from zero.
+    Nearest,
+}
+
+#[derive(Clone)]
+enum Step {
+    Linear {
+        divisor: i64,
+        subtraction: i64,
+        rounding: Rounding,
+    },
+    Custom(Arc<dyn Fn(i64) -> i64 + Send + Sync>),
+}
+
+#[derive(Clone)]
+pub struct FuelFormula {
+    step: Step,
+    threshold: i64,
+}
+
+impl fmt::Debug for FuelFormula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.step {
+            Step::Linear {
+                divisor,
+                subtraction,
+                rounding,
+            } => write!(
+                f,
+                "FuelFormula(mass / {} ({:?}) - {}, threshold {})",
+                divisor, rounding, subtraction, self.threshold
+            ),
+            Step::Custom(_) => write!(f, "FuelFormula(custom, threshold {})", self.threshold),
+        }
+    }
+}
+
+impl Default for FuelFormula {
+    /// `mass / 3 - 2`, rounded toward zero.
+    fn default() -> Self {
+        Self::new(3, 2).unwrap()
+    }
+}
+
+impl FuelFormula {
+    /// `mass / divisor - subtraction`, rounded toward zero, with a threshold of 0.
+    pub fn new(divisor: i64, subtraction: i64) -> Result<Self, String> {
+        if divisor <= 0 {
+            return Err(format!("Divisor must be positive, got {}.", divisor));
+        }
+        Ok(Self {
+            step: Step::Linear {
+                divisor,
+                subtraction,
+                rounding: Rounding::TowardZero,
+            },
+            threshold: 0,
+        })
+    }
+
+    /// A formula that computes the fuel for a mass with `f`.
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(i64) -> i64 + Send + Sync + 'static,
+    {
+        Self {
+            step: Step::Custom(Arc::new(f)),
+            threshold: 0,
+        }
+    }
+
+    /// Changes how linear formulas round. Custom formulas are unaffected.
+    pub fn rounding(mut self, new_rounding: Rounding) -> Self {
+        if let Step::Linear { rounding, .. } = &mut self.step {
+            *rounding = new_rounding;
+        }
+        self
+    }
+
+    /// Extra fuel at or below `threshold` is not added to the recursive fuel.
+    pub fn threshold(self, threshold: i64) -> Self {
+        Self { threshold, ..self }
+    }
+
+    /// The fuel for `mass` alone.
+    pub fn naive(&self, mass: i64) -> Result<i64, String> {
+        match &self.step {
+            Step::Linear {
+                divisor,
+                subtraction,
+                rounding,
+            } => divide(mass, *divisor, *rounding)
+                .checked_sub(*subtraction)
+                .ok_or_else(overflow),
+            Step::Custom(f) => Ok(f(mass)),
+        }
+    }
+
+    /// The fuel requirements of a module of `mass`. Fails if the extra fuel
+    /// never drops to the threshold, or if the total overflows.
+    pub fn module(&self, mass: i64) -> Result<ModuleFuel, String> {
+        let naive = self.naive(mass)?;
+        let (recursive, steps) = match self.step {
+            Step::Linear {
+                divisor: 1,
+                subtraction,
+                ..
+            } => self.linear_closed_form(naive, subtraction)?,
+            _ => self.iterate(naive)?,
+        };
+        Ok(ModuleFuel {
+            mass,
+            naive,
+            recursive,
+            steps,
+        })
+    }
+
+    fn iterate(&self, naive: i64) -> Result<(i64, u64), String> {
+        let mut recursive = naive;
+        let mut steps = 1;
+        let mut fuel = naive;
+        loop {
+            let extra = self.naive(fuel)?;
+            if extra <= self.threshold {
+                return Ok((recursive, steps));
+            }
+            if extra >= fuel {
+                return Err(not_converging(fuel, extra));
+            }
+            recursive = recursive.checked_add(extra).ok_or_else(overflow)?;
+            steps += 1;
+            fuel = extra;
+        }
+    }
+
+    /// With a divisor of 1 the extra fuel shrinks by `subtraction` each step, so
+    /// the recursive fuel is an arithmetic series. Summing it step by step
+    /// would take `mass / subtraction` steps.
+    fn linear_closed_form(&self, naive: i64, subtraction: i64) -> Result<(i64, u64), String> {
+        let extra = naive.checked_sub(subtraction).ok_or_else(overflow)?;
+        if extra <= self.threshold {
+            return Ok((naive, 1));
+        }
+        if subtraction <= 0 {
+            return Err(not_converging(naive, extra));
+        }
+        let (naive, subtraction) = (i128::from(naive), i128::from(subtraction));
+        // The number of extra amounts `naive - k * subtraction` above the threshold.
+        let extras = (naive - i128::from(self.threshold) - 1) / subtraction;
+        let recursive = naive * (extras + 1) - subtraction * extras * (extras + 1) / 2;
+        let recursive = i64::try_from(recursive).map_err(|_| overflow())?;
+        Ok((recursive, extras as u64 + 1))
+    }
+}
+
+fn divide(mass: i64, divisor: i64, rounding: Rounding) -> i64 {
+    let (quotient, remainder) = (mass.div_euclid(divisor), mass.rem_euclid(divisor));
+    match rounding {
+        Rounding::TowardZero => mass / divisor,
+        Rounding::Down => quotient,
+        Rounding::Up if remainder > 0 => quotient + 1,
+        Rounding::Up => quotient,
+        Rounding::Nearest if 2 * i128::from(remainder) > i128::from(divisor) => quotient + 1,
+        Rounding::Nearest if 2 * i128::from(remainder) == i128::from(divisor) && mass >= 0 => {
+            quotient + 1
+        }
+        Rounding::Nearest => quotient,
+    }
+}
+
+fn not_converging(fuel: i64, extra: i64) -> String {
+    format!(
+        "The fuel formula does not converge: {} fuel needs {} more.",
+        fuel, extra
+    )
+}
+
+pub(crate) fn overflow() -> String {
+    String::from("Fuel overflows 64-bit arithmetic.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_formula_matches_puzzle() {
+        let formula = FuelFormula::default();
+        assert_eq!(formula.naive(12), Ok(2));
+        assert_eq!(formula.naive(2), Ok(-2));
+        assert_eq!(formula.module(100756).unwrap(), ModuleFuel::new(100756));
+    }
+
+    #[test]
+    fn rounding_modes() {
+        let formula = |rounding| FuelFormula::new(4, 0).unwrap().rounding(rounding);
+        let naive = |rounding, mass| formula(rounding).naive(mass).unwrap();
+        assert_eq!(naive(Rounding::TowardZero, -6), -1);
+        assert_eq!(naive(Rounding::Down, -6), -2);
+        assert_eq!(naive(Rounding::Up, 5), 2);
+        assert_eq!(naive(Rounding::Up, 8), 2);
+        assert_eq!(naive(Rounding::Nearest, 5), 1);
+        assert_eq!(naive(Rounding::Nearest, 6), 2);
+        assert_eq!(naive(Rounding::Nearest, -6), -2);
+        assert_eq!(naive(Rounding::Nearest, -7), -2);
+    }
+
+    #[test]
+    fn threshold_stops_recursion() {
+        let formula = FuelFormula::default().threshold(10);
+        // 654 + 216 + 70 + 21, dropping the final 5.
+        assert_eq!(formula.module(1969).unwrap().recursive, 961);
+        assert_eq!(formula.module(1969).unwrap().steps, 4);
+    }
+
+    #[test]
+    fn custom_formula() {
+        let formula = FuelFormula::custom(|mass| mass / 4 - 1);
+        let module = formula.module(1000).unwrap();
+        assert_eq!((module.naive, module.recursive), (249, 249 + 61 + 14 + 2));
+    }
+
+    #[test]
+    fn closed_form_matches_iteration() {
+        for subtraction in 1..6 {
+            for threshold in -3..4 {
+                let linear = FuelFormula::new(1, subtraction)
+                    .unwrap()
+                    .threshold(threshold);
+                let custom =
+                    FuelFormula::custom(move |mass| mass - subtraction).threshold(threshold);
+                for mass in -10..200 {
+                    assert_eq!(linear.module(mass), custom.module(mass));
+                }
+            }
+        }
+        let module = FuelFormula::new(1, 1).unwrap().module(60_000).unwrap();
+        assert_eq!((module.recursive, module.steps), (1_799_970_000, 59_999));
+        let module = FuelFormula::new(1, 1)
+            .unwrap()
+            .module(4_000_000_000)
+            .unwrap();
+        assert_eq!(module.recursive, 3_999_999_999 * 2_000_000_000);
+        assert_eq!(
+            FuelFormula::new(1, 1).unwrap().module(i64::MAX),
+            Err(overflow())
+        );
+        assert_eq!(
+            FuelFormula::new(1, 5).unwrap().naive(i64::MIN),
+            Err(overflow())
+        );
+    }
+
+    #[test]
+    fn invalid_formulas_are_rejected() {
+        assert!(FuelFormula::new(0, 2).is_err());
+        // mass / 3 + 5 settles on 7 and never drops to 0.
+        let error = FuelFormula::new(3, -5).unwrap().module(100).unwrap_err();
+        assert_eq!(
+            error,
+            "The fuel formula does not converge: 7 fuel needs 7 more."
+        );
+        assert!(FuelFormula::new(1, 0).unwrap().module(100).is_err());
+    }
+}
@@ -1,49 +1,40 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader, Error};
+use std::fs;
 
-fn read_file(path: &str) -> Result<BufReader<File>, Error> {
-    let file = File::open(path)?;
-    Ok(BufReader::new(file))
-}
-
-fn naive_fuel_cost(mass: i32) -> i32 {
-    mass / 3 - 2
-}
+pub mod cli;
+mod formula;
+mod parse;
+mod plan;
+mod stream;
 
-fn fuel_cost(mass: i32) -> i32 {
-    let mut cost = naive_fuel_cost(mass);
-    let mut incremental_cost = naive_fuel_cost(cost);
+pub use formula::{FuelFormula, Rounding};
+pub use parse::{parse_masses, MassError, MassErrorKind, ParsedMasses};
+pub use plan::{FuelPlan, ModuleFuel};
+pub use stream::{parallel_totals, stream_totals, total_fuel_cost, FuelTotals};
 
-    while incremental_cost > 0 {
-        cost += incremental_cost;
-        incremental_cost = naive_fuel_cost(incremental_cost);
-    }
-
-    cost
+fn read_masses(path: &str) -> Result<Vec<i64>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Cannot read input: {}", error))?;
+    parse_masses(&text)
+        .into_result()
+        .map_err(|error| error.to_string())
 }
 
-fn naive_total_fuel_cost(reader: BufReader<File>) -> i32 {
-    reader
-        .lines()
-        .map(|line| naive_fuel_cost(line.unwrap().parse().unwrap()))
-        .sum()
+pub fn naive_fuel_cost(mass: i64) -> Result<i64, String> {
+    FuelFormula::default().naive(mass)
 }
 
-fn total_fuel_cost(reader: BufReader<File>) -> i32 {
-    reader
-        .lines()
-        .map(|line| fuel_cost(line.unwrap().parse().unwrap()))
-        .sum()
+/// The fuel for `mass`, plus the fuel for that fuel and so on.
+pub fn fuel_cost(mass: i64) -> Result<i64, String> {
+    FuelFormula::default()
+        .module(mass)
+        .map(|module| module.recursive)
 }
 
-pub fn pt1(path: &str) -> i32 {
-    let reader = read_file(&path).unwrap();
-    naive_total_fuel_cost(reader)
+pub fn pt1(path: &str) -> Result<i64, String> {
+    FuelPlan::new(read_masses(path)?).naive_total()
 }
 
-pub fn pt2(path: &str) -> i32 {
-    let reader = read_file(&path).unwrap();
-    total_fuel_cost(reader)
+pub fn pt2(path: &str) -> Result<i64, String> {
+    FuelPlan::new(read_masses(path)?).recursive_total()
 }
 
 #[cfg(test)]
@@ -52,11 +43,32 @@ mod tests {
 
     #[test]
     fn pt1_test() {
-        assert_eq!(3402609, pt1("input"));
+        assert_eq!(Ok(3402609), pt1("input"));
     }
 
     #[test]
     fn pt2_test() {
-        assert_eq!(5101025, pt2("input"));
+        assert_eq!(Ok(5101025), pt2("input"));
+    }
+
+    #[test]
+    fn single_module_costs() {
+        assert_eq!(naive_fuel_cost(1969), Ok(654));
+        assert_eq!(fuel_cost(1969), Ok(966));
+        assert_eq!(fuel_cost(100756), Ok(50346));
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        assert!(pt1("missing")
+            .unwrap_err()
+            .starts_with("Cannot read input: "));
+        let path =
+            std::env::temp_dir().join(format!("day1_bad_input_is_an_error_{}", std::process::id()));
+        fs::write(&path, "12\n1x4\n").unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(pt1(path), Err(String::from("line 2: `1x4` is not a mass")));
+        assert_eq!(pt2(path), Err(String::from("line 2: `1x4` is not a mass")));
+        fs::remove_file(path).unwrap();
     }
 }
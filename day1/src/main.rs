@@ -1,10 +1,73 @@
-use day1::*;
-use std::env;
+use day1::cli::{self, Command, Options};
+use day1::{parallel_totals, parse_masses, stream_totals, FuelFormula, FuelPlan};
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+/// Exit code for bad command lines.
+const USAGE_ERROR: i32 = 2;
+/// Exit code for unreadable or invalid input, including totals that overflow.
+const INPUT_ERROR: i32 = 1;
 
 fn main() {
-    let path: String = env::args().skip(1).next().unwrap();
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            process::exit(USAGE_ERROR);
+        }
+    };
+
+    if !options.per_module {
+        let totals = match (&options.path, options.threads) {
+            (Some(path), 1) => fs::File::open(path)
+                .map_err(|error| format!("Cannot read input: {}", error))
+                .and_then(|file| stream_totals(file, &FuelFormula::default())),
+            (None, 1) => stream_totals(io::stdin().lock(), &FuelFormula::default()),
+            (_, threads) => {
+                parallel_totals(&read_input(&options), &FuelFormula::default(), threads)
+            }
+        };
+        match totals {
+            Ok(totals) => print!("{}", cli::render_totals(&totals, &options)),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(INPUT_ERROR);
+            }
+        }
+        return;
+    }
 
-    let result = pt2(&path);
+    let parsed = parse_masses(&read_input(&options));
+    if !parsed.errors.is_empty() {
+        for error in &parsed.errors {
+            eprintln!("{}", error);
+        }
+        process::exit(INPUT_ERROR);
+    }
+    match cli::render(&FuelPlan::new(parsed.masses), &options) {
+        Ok(output) => print!("{}", output),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(INPUT_ERROR);
+        }
+    }
+}
 
-    println!("{}", result);
+/// The whole input, from the file or standard input.
+fn read_input(options: &Options) -> String {
+    let text = match &options.path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    };
+    text.unwrap_or_else(|error| {
+        eprintln!("Cannot read input: {}", error);
+        process::exit(INPUT_ERROR);
+    })
 }
@@ -0,0 +1,160 @@
+//! Validated parsing of mass lists.
+//!
+//! Masses are separated by whitespace, commas or newlines. Blank lines are
+//! skipped, and `#` starts a comment that runs to the end of the line. Bad
+//! entries are collected along with their line numbers instead of aborting,
+//! so that every problem in a file can be reported at once.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MassErrorKind {
+    /// The entry is not an integer.
+    Invalid,
+    /// The entry is an integer, but does not fit the mass type.
+    OutOfRange,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MassError {
+    /// 1-based line number.
+    pub line: usize,
+    pub entry: String,
+    pub kind: MassErrorKind,
+}
+
+impl fmt::Display for MassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            MassErrorKind::Invalid => {
+                write!(f, "line {}: `{}` is not a mass", self.line, self.entry)
+            }
+            MassErrorKind::OutOfRange => {
+                write!(f, "line {}: `{}` is out of range", self.line, self.entry)
+            }
+        }
+    }
+}
+
+/// The masses that parsed, in order, along with every entry that did not.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedMasses<T> {
+    pub masses: Vec<T>,
+    pub errors: Vec<MassError>,
+}
+
+impl<T> ParsedMasses<T> {
+    /// The masses, or the first error if there were any.
+    pub fn into_result(self) -> Result<Vec<T>, MassError> {
+        match self.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(self.masses),
+        }
+    }
+}
+
+/// Parses every mass in `text` into a `T`. Entries may be as large as an `i128`.
+pub fn parse_masses<T: TryFrom<i128>>(text: &str) -> ParsedMasses<T> {
+    let mut parsed = ParsedMasses {
+        masses: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (index, line) in text.lines().enumerate() {
+        parse_line(index + 1, line, &mut parsed);
+    }
+    parsed
+}
+
+/// Parses the masses on line `number` into `parsed`.
+pub(crate) fn parse_line<T: TryFrom<i128>>(
+    number: usize,
+    line: &str,
+    parsed: &mut ParsedMasses<T>,
+) {
+    for entry in parse_entries(number, line) {
+        match entry {
+            Ok(mass) => parsed.masses.push(mass),
+            Err(error) => parsed.errors.push(error),
+        }
+    }
+}
+
+/// Every entry on line `number`, parsed, from left to right.
+pub(crate) fn parse_entries<T: TryFrom<i128>>(
+    number: usize,
+    line: &str,
+) -> impl Iterator<Item = Result<T, MassError>> + '_ {
+    let content = line.split('#').next().unwrap();
+    content
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .map(move |entry| {
+            let kind = match entry.parse::<i128>() {
+                Ok(value) => match T::try_from(value) {
+                    Ok(mass) => return Ok(mass),
+                    Err(_) => MassErrorKind::OutOfRange,
+                },
+                Err(_) if is_integer(entry) => MassErrorKind::OutOfRange,
+                Err(_) => MassErrorKind::Invalid,
+            };
+            Err(MassError {
+                line: number,
+                entry: entry.to_string(),
+                kind,
+            })
+        })
+}
+
+fn is_integer(entry: &str) -> bool {
+    let digits = entry.strip_prefix(['+', '-']).unwrap_or(entry);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_blank_lines_and_comments() {
+        let text = "# fleet 1\n12, 14\n\n  1969\t100756 # heavy\n,\n";
+        let parsed = parse_masses::<i32>(text);
+        assert_eq!(parsed.masses, vec![12, 14, 1969, 100756]);
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn bad_entries_are_reported_with_line_numbers() {
+        let parsed = parse_masses::<i32>("12\n1x4\n3000000000 7\n-5");
+        assert_eq!(parsed.masses, vec![12, 7, -5]);
+        assert_eq!(
+            parsed.errors,
+            vec![
+                MassError {
+                    line: 2,
+                    entry: String::from("1x4"),
+                    kind: MassErrorKind::Invalid
+                },
+                MassError {
+                    line: 3,
+                    entry: String::from("3000000000"),
+                    kind: MassErrorKind::OutOfRange
+                },
+            ]
+        );
+        assert_eq!(parsed.errors[0].to_string(), "line 2: `1x4` is not a mass");
+        assert!(parse_masses::<i32>("1x4").into_result().is_err());
+    }
+
+    #[test]
+    fn wide_magnitudes() {
+        let parsed = parse_masses::<u64>("18446744073709551615 -1");
+        assert_eq!(parsed.masses, vec![u64::MAX]);
+        assert_eq!(parsed.errors[0].kind, MassErrorKind::OutOfRange);
+
+        let huge = "170141183460469231731687303715884105727";
+        assert_eq!(parse_masses::<i128>(huge).masses, vec![i128::MAX]);
+        let parsed = parse_masses::<i128>("170141183460469231731687303715884105728");
+        assert_eq!(parsed.errors[0].kind, MassErrorKind::OutOfRange);
+    }
+}
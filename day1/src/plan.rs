@@ -0,0 +1,224 @@
+//! Per-module fuel requirements, so that it is easy to see which modules
+//! dominate the fuel budget.
+
+use crate::formula::{overflow, FuelFormula};
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::iter::FromIterator;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModuleFuel {
+    pub mass: i64,
+    /// Fuel for the module's mass alone.
+    pub naive: i64,
+    /// Fuel for the module's mass, plus the fuel for that fuel and so on.
+    pub recursive: i64,
+    /// How many fuel amounts `recursive` adds up, starting with `naive`.
+    pub steps: u64,
+}
+
+impl ModuleFuel {
+    /// The fuel requirements of a module of `mass`, with the puzzle's formula.
+    /// That formula cannot overflow, since the fuel is less than the mass.
+    pub fn new(mass: i64) -> Self {
+        FuelFormula::default().module(mass).unwrap()
+    }
+}
+
+/// The fuel requirements of every module, in the order they were given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FuelPlan {
+    pub modules: Vec<ModuleFuel>,
+}
+
+impl FuelPlan {
+    pub fn new<I: IntoIterator<Item = i64>>(masses: I) -> Self {
+        masses.into_iter().collect()
+    }
+
+    pub fn with_formula<I: IntoIterator<Item = i64>>(
+        masses: I,
+        formula: &FuelFormula,
+    ) -> Result<Self, String> {
+        let modules = masses
+            .into_iter()
+            .map(|mass| formula.module(mass))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { modules })
+    }
+
+    pub fn total_mass(&self) -> Result<i64, String> {
+        checked_sum(self.modules.iter().map(|module| module.mass))
+    }
+
+    pub fn naive_total(&self) -> Result<i64, String> {
+        checked_sum(self.modules.iter().map(|module| module.naive))
+    }
+
+    pub fn recursive_total(&self) -> Result<i64, String> {
+        checked_sum(self.modules.iter().map(|module| module.recursive))
+    }
+
+    pub fn total_steps(&self) -> u64 {
+        self.modules.iter().map(|module| module.steps).sum()
+    }
+
+    /// The `n` modules that need the most fuel, most demanding first.
+    pub fn most_demanding(&self, n: usize) -> Vec<&ModuleFuel> {
+        let mut modules: Vec<&ModuleFuel> = self.modules.iter().collect();
+        modules.sort_by_key(|module| Reverse(module.recursive));
+        modules.truncate(n);
+        modules
+    }
+}
+
+impl FromIterator<i64> for FuelPlan {
+    fn from_iter<I: IntoIterator<Item = i64>>(masses: I) -> Self {
+        Self {
+            modules: masses.into_iter().map(ModuleFuel::new).collect(),
+        }
+    }
+}
+
+/// Sums in 128 bits, so that only the total has to fit and the order of the
+/// values does not matter.
+fn checked_sum<I: Iterator<Item = i64>>(values: I) -> Result<i64, String> {
+    narrow(values.map(i128::from).sum())
+}
+
+pub(crate) fn narrow(total: i128) -> Result<i64, String> {
+    i64::try_from(total).map_err(|_| overflow())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::Rounding;
+
+    #[test]
+    fn module_fuel_examples() {
+        assert_eq!(
+            ModuleFuel::new(14),
+            ModuleFuel {
+                mass: 14,
+                naive: 2,
+                recursive: 2,
+                steps: 1
+            }
+        );
+        let module = ModuleFuel::new(1969);
+        assert_eq!(
+            (module.naive, module.recursive, module.steps),
+            (654, 966, 5)
+        );
+        let module = ModuleFuel::new(100756);
+        assert_eq!((module.naive, module.recursive), (33583, 50346));
+    }
+
+    #[test]
+    fn plan_aggregates_modules() {
+        let plan: FuelPlan = vec![12, 14, 1969, 100756].into_iter().collect();
+        assert_eq!(plan.total_mass(), Ok(102751));
+        assert_eq!(plan.naive_total(), Ok(2 + 2 + 654 + 33583));
+        assert_eq!(plan.recursive_total(), Ok(2 + 2 + 966 + 50346));
+        assert_eq!(plan.total_steps(), 1 + 1 + 5 + 9);
+        let masses: Vec<i64> = plan.most_demanding(2).iter().map(|m| m.mass).collect();
+        assert_eq!(masses, vec![100756, 1969]);
+        assert_eq!(FuelPlan::new(Vec::new()).recursive_total(), Ok(0));
+    }
+
+    #[test]
+    fn totals_report_overflow() {
+        let plan = FuelPlan::new(vec![i64::MAX / 2; 5]);
+        assert!(plan.naive_total().is_ok());
+        assert_eq!(plan.recursive_total(), Err(overflow()));
+        assert_eq!(plan.total_mass(), Err(overflow()));
+        let plan = FuelPlan::new(vec![i64::MAX, 1, -5]);
+        assert_eq!(plan.total_mass(), Ok(i64::MAX - 4));
+    }
+
+    /// xorshift64, so that the property tests are reproducible without extra
+    /// dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A mass of any magnitude, favouring small ones.
+        fn mass(&mut self) -> i64 {
+            let bits = self.next() % 64;
+            (self.next() >> (64 - bits.max(1))) as i64 - 10
+        }
+    }
+
+    fn random_formula(rng: &mut Rng) -> FuelFormula {
+        let rounding = [
+            Rounding::TowardZero,
+            Rounding::Down,
+            Rounding::Up,
+            Rounding::Nearest,
+        ][rng.next() as usize % 4];
+        let divisor = 1 + rng.next() % 10;
+        let subtraction = (rng.next() % 10) + (divisor == 1) as u64;
+        FuelFormula::new(divisor as i64, subtraction as i64)
+            .unwrap()
+            .rounding(rounding)
+            .threshold((rng.next() % 20) as i64)
+    }
+
+    #[test]
+    fn recursive_fuel_is_monotonic_and_at_least_naive() {
+        let mut rng = Rng(0x00f0_e1f0_e1f0_e1f0);
+        let formulas: Vec<FuelFormula> = Some(FuelFormula::default())
+            .into_iter()
+            .chain((0..50).map(|_| random_formula(&mut rng)))
+            .collect();
+        for formula in &formulas {
+            for _ in 0..1_000 {
+                let (a, b) = (rng.mass(), rng.mass());
+                let (light, heavy) = (a.min(b), a.max(b));
+                let (light, heavy) = match (formula.module(light), formula.module(heavy)) {
+                    (Ok(light), Ok(heavy)) => (light, heavy),
+                    _ => continue,
+                };
+                assert!(
+                    light.recursive <= heavy.recursive,
+                    "{:?} {:?}",
+                    formula,
+                    (a, b)
+                );
+                assert!(
+                    light.recursive >= light.naive,
+                    "{:?} {}",
+                    formula,
+                    light.mass
+                );
+                assert!(
+                    heavy.recursive >= heavy.naive,
+                    "{:?} {}",
+                    formula,
+                    heavy.mass
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn default_formula_never_overflows() {
+        let mut rng = Rng(0x5eed);
+        let masses: Vec<i64> = (0..10_000)
+            .map(|_| rng.mass())
+            .chain(vec![i64::MIN, i64::MAX])
+            .collect();
+        for &mass in &masses {
+            assert!(FuelFormula::default().module(mass).is_ok(), "{}", mass);
+        }
+        let plan = FuelPlan::new(masses.into_iter().filter(|&mass| mass < 1 << 40));
+        assert!(plan.recursive_total().unwrap() >= plan.naive_total().unwrap());
+    }
+}
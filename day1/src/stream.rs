@@ -0,0 +1,286 @@
+//! Fuel totals for manifests too large to keep every module around.
+//!
+//! Masses are parsed and turned into fuel as they are read, and only the sums
+//! are kept. Sums are accumulated in 128 bits and narrowed at the end, so the
+//! sequential, streaming and parallel totals agree no matter how the input is
+//! split up. Every bad entry and every module whose fuel cannot be computed is
+//! reported, in input order, with its line number. Errors on the same line
+//! come in the order of their entries.
+
+use crate::formula::FuelFormula;
+use crate::parse::parse_entries;
+use crate::plan::narrow;
+use std::io::{BufRead, BufReader, Read};
+use std::thread;
+
+/// The summed fuel requirements of a manifest.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FuelTotals {
+    pub modules: u64,
+    pub naive: i64,
+    pub recursive: i64,
+}
+
+struct Accumulator<'a> {
+    formula: &'a FuelFormula,
+    modules: u64,
+    naive: i128,
+    recursive: i128,
+    errors: Vec<String>,
+}
+
+impl<'a> Accumulator<'a> {
+    fn new(formula: &'a FuelFormula) -> Self {
+        Self {
+            formula,
+            modules: 0,
+            naive: 0,
+            recursive: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn add_line(&mut self, number: usize, line: &str) {
+        for entry in parse_entries(number, line) {
+            let module = entry.map_err(|error| error.to_string()).and_then(|mass| {
+                self.formula
+                    .module(mass)
+                    .map_err(|error| format!("line {}: {}", number, error))
+            });
+            match module {
+                Ok(module) => {
+                    self.modules += 1;
+                    self.naive += i128::from(module.naive);
+                    self.recursive += i128::from(module.recursive);
+                }
+                Err(error) => self.errors.push(error),
+            }
+        }
+    }
+
+    /// Adds the totals and errors of `later`, which covers the lines after this one's.
+    fn merge(&mut self, later: Self) {
+        self.modules += later.modules;
+        self.naive += later.naive;
+        self.recursive += later.recursive;
+        self.errors.extend(later.errors);
+    }
+
+    /// The totals, or every error joined by newlines.
+    fn finish(self) -> Result<FuelTotals, String> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.join("\n"));
+        }
+        Ok(FuelTotals {
+            modules: self.modules,
+            naive: narrow(self.naive)?,
+            recursive: narrow(self.recursive)?,
+        })
+    }
+}
+
+/// The fuel totals of the masses in `text`, one line after another.
+pub fn total_fuel_cost(text: &str, formula: &FuelFormula) -> Result<FuelTotals, String> {
+    let mut totals = Accumulator::new(formula);
+    for (index, line) in text.lines().enumerate() {
+        totals.add_line(index + 1, line);
+    }
+    totals.finish()
+}
+
+/// Like `total_fuel_cost`, but reads the masses from `reader` a line at a time.
+pub fn stream_totals<R: Read>(reader: R, formula: &FuelFormula) -> Result<FuelTotals, String> {
+    let mut reader = BufReader::new(reader);
+    let mut totals = Accumulator::new(formula);
+    let mut line = String::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return totals.finish(),
+            Ok(_) => {
+                number += 1;
+                totals.add_line(number, &line);
+            }
+            Err(error) => return Err(format!("Cannot read input: {}", error)),
+        }
+    }
+}
+
+/// Like `total_fuel_cost`, but splits `text` into a chunk of lines per thread.
+pub fn parallel_totals(
+    text: &str,
+    formula: &FuelFormula,
+    threads: usize,
+) -> Result<FuelTotals, String> {
+    let chunks = split_lines(text, threads.max(1));
+    let partials: Vec<Accumulator> = thread::scope(|scope| {
+        let workers: Vec<_> = chunks
+            .iter()
+            .map(|&(first_line, chunk)| {
+                scope.spawn(move || {
+                    let mut totals = Accumulator::new(formula);
+                    for (index, line) in chunk.lines().enumerate() {
+                        totals.add_line(first_line + index, line);
+                    }
+                    totals
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    let mut totals = Accumulator::new(formula);
+    for partial in partials {
+        totals.merge(partial);
+    }
+    totals.finish()
+}
+
+/// Splits `text` into `n` runs of whole lines of about the same size, each
+/// with the number of its first line.
+fn split_lines(text: &str, n: usize) -> Vec<(usize, &str)> {
+    let mut chunks = Vec::with_capacity(n);
+    let mut rest = text;
+    let mut first_line = 1;
+    for remaining in (1..=n).rev() {
+        let target = rest.len() / remaining;
+        let end = match rest.as_bytes()[target..].iter().position(|&b| b == b'\n') {
+            Some(newline) if remaining > 1 => target + newline + 1,
+            _ => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push((first_line, chunk));
+        first_line += chunk.bytes().filter(|&b| b == b'\n').count();
+        rest = tail;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FuelPlan;
+    use std::fs;
+
+    /// xorshift64, for reproducible manifests.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    /// A manifest with separators, comments, blank lines, CRLF line endings
+    /// and, if `bad` is set, the odd bad entry.
+    fn manifest(rng: &mut Rng, lines: usize, bad: bool) -> String {
+        let mut text = String::new();
+        for _ in 0..lines {
+            match rng.next() % 20 {
+                0 => text.push_str("# comment, 12"),
+                1 => {}
+                2 => text.push_str("  12 , 1969\t100756 # three"),
+                3 if bad => text.push_str("1x4"),
+                4 if bad => text.push_str("99999999999999999999"),
+                5 => text.push('\r'),
+                _ => text.push_str(&(rng.next() % 1_000_000).to_string()),
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn puzzle_input() {
+        let text = fs::read_to_string("input").unwrap();
+        let formula = FuelFormula::default();
+        let totals = total_fuel_cost(&text, &formula).unwrap();
+        assert_eq!((totals.naive, totals.recursive), (3402609, 5101025));
+        assert_eq!(totals.modules, 100);
+        assert_eq!(stream_totals(text.as_bytes(), &formula), Ok(totals));
+        assert_eq!(parallel_totals(&text, &formula, 4), Ok(totals));
+    }
+
+    #[test]
+    fn all_modes_agree() {
+        let mut rng = Rng(0x0f0e_1f0e);
+        let formulas = [
+            FuelFormula::default(),
+            FuelFormula::new(1, 7).unwrap().threshold(5),
+        ];
+        for round in 0..40 {
+            let lines = rng.next() as usize % 300;
+            let text = manifest(&mut rng, lines, round % 2 == 0);
+            for formula in formulas.iter() {
+                let expected = total_fuel_cost(&text, formula);
+                assert_eq!(stream_totals(text.as_bytes(), formula), expected);
+                for threads in 1..=9 {
+                    assert_eq!(parallel_totals(&text, formula, threads), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_fuel_plan() {
+        let mut rng = Rng(0x5eed);
+        let text = manifest(&mut rng, 1_000, false);
+        let plan = FuelPlan::new(crate::parse_masses::<i64>(&text).into_result().unwrap());
+        let totals = parallel_totals(&text, &FuelFormula::default(), 3).unwrap();
+        assert_eq!(totals.modules, plan.modules.len() as u64);
+        assert_eq!(Ok(totals.naive), plan.naive_total());
+        assert_eq!(Ok(totals.recursive), plan.recursive_total());
+    }
+
+    #[test]
+    fn errors_are_reported_in_order() {
+        let text = "12\n1x4\n\n-7 # fine\n10 y 2\n";
+        let formula = FuelFormula::new(2, -1).unwrap();
+        let expected = "line 1: The fuel formula does not converge: 2 fuel needs 2 more.\n\
+                        line 2: `1x4` is not a mass\n\
+                        line 5: The fuel formula does not converge: 2 fuel needs 2 more.\n\
+                        line 5: `y` is not a mass\n\
+                        line 5: The fuel formula does not converge: 2 fuel needs 2 more.";
+        assert_eq!(total_fuel_cost(text, &formula), Err(String::from(expected)));
+        for threads in 1..=6 {
+            assert_eq!(
+                parallel_totals(text, &formula, threads),
+                Err(String::from(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn only_the_final_total_has_to_fit() {
+        let text = format!("{}\n{}\n-{}\n", i64::MAX, i64::MAX, i64::MAX);
+        let formula = FuelFormula::custom(|mass| mass).threshold(i64::MAX);
+        let totals = total_fuel_cost(&text, &formula).unwrap();
+        assert_eq!(totals.recursive, i64::MAX);
+        assert_eq!(parallel_totals(&text, &formula, 2), Ok(totals));
+        let text = format!("{}\n{}\n", i64::MAX, i64::MAX);
+        assert!(total_fuel_cost(&text, &formula).is_err());
+        assert!(parallel_totals(&text, &formula, 2).is_err());
+    }
+
+    #[test]
+    fn chunks_cover_every_line() {
+        let text = "1\n22\n333\n4444\n";
+        for n in 1..8 {
+            let chunks = split_lines(text, n);
+            assert_eq!(chunks.len(), n);
+            let joined: String = chunks.iter().map(|&(_, chunk)| chunk).collect();
+            assert_eq!(joined, text);
+            for &(first_line, chunk) in &chunks {
+                if let Some(line) = chunk.lines().next() {
+                    assert_eq!(line.len(), first_line);
+                }
+            }
+        }
+    }
+}
@@ -1,222 +1,434 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Error};
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+
+mod places;
+mod sweep;
+
+use places::wire_crossings;
+pub use places::{Place, WireCrossing};
 
 #[derive(Debug, Clone, PartialEq)]
-struct Point {
+pub struct Point {
     pub x: i32,
-    pub y: i32
+    pub y: i32,
 }
 
 impl Point {
-    fn transform(&self, v: &Vector) -> Point {
-        match v {
-            Vector::Up(delta_y) => Point::new(self.x, self.y + delta_y),
-            Vector::Right(delta_x) => Point::new(self.x + delta_x, self.y),
-        }
+    /// This point moved by `v`, or `None` if that is off the grid.
+    fn transform(&self, v: &Vector) -> Option<Point> {
+        let coordinate =
+            |start: i32, step: i64| i32::try_from(i64::from(start).checked_add(step)?).ok();
+        Some(Point::new(
+            coordinate(self.x, v.x)?,
+            coordinate(self.y, v.y)?,
+        ))
     }
 
-    fn distance_from_origin(&self) -> i32 {
-        self.x.abs() + self.y.abs()
+    pub fn distance_from_origin(&self) -> i64 {
+        i64::from(self.x).abs() + i64::from(self.y).abs()
     }
 
-    fn distance_from_point(&self, p: &Self) -> i32 {
-        (self.x - p.x).abs() + (self.y - p.y).abs()
+    /// The vector from `p` to this point.
+    fn minus(&self, p: &Self) -> Vector {
+        Vector::new(
+            i64::from(self.x) - i64::from(p.x),
+            i64::from(self.y) - i64::from(p.y),
+        )
     }
 
     fn new(x: i32, y: i32) -> Self {
-        Self{x, y}
+        Self { x, y }
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum Direction {
-    Horizontal,
-    Vertical,
-}
-
 #[derive(Debug, PartialEq)]
 struct LineSegment {
     pub head: Point,
-    pub tail: Point
+    pub tail: Point,
 }
 
 impl LineSegment {
-    fn direction(&self) -> Direction {
-        if self.tail.x == self.head.x {
-            Direction::Vertical
-        } else if self.tail.y == self.head.y {
-            Direction::Horizontal
+    /// The smallest step between grid points along the segment, e.g. (1, 0)
+    /// for a segment going right, or (1, 2) for one going along (3, 6).
+    /// Zero-length segments have a direction of (0, 0).
+    fn direction(&self) -> Vector {
+        let v = self.head.minus(&self.tail);
+        let steps = v.steps();
+        if steps == 0 {
+            v
         } else {
-            panic!("Cannot handle diagonal line")
+            Vector::new(v.x / steps, v.y / steps)
         }
     }
 
-    fn intersection(&self, other: &Self) -> Option<Point> {
-        if self.direction() == other.direction() {
-            None
-        } else if self.direction() == Direction::Horizontal
-            && other.tail.x <= cmp::max(self.tail.x, self.head.x)
-            && other.tail.x >= cmp::min(self.tail.x, self.head.x)
-            && self.tail.y <= cmp::max(other.tail.y, other.head.y)
-            && self.tail.y >= cmp::min(other.tail.y, other.head.y)
-        {
-            Some(Point::new(other.tail.x, self.tail.y))
-        } else if self.direction() == Direction::Vertical
-        {
-            other.intersection(self)
+    /// Grid points where both segments are. Segments only meet at grid
+    /// points, so diagonal segments that cross between grid points do not
+    /// intersect. Collinear segments that share more than one point overlap
+    /// along a segment running the same way as `self`.
+    fn intersection(&self, other: &Self) -> Option<Intersection> {
+        let (v1, v2) = (self.head.minus(&self.tail), other.head.minus(&other.tail));
+        if v1.steps() == 0 || v2.steps() == 0 {
+            let p = if v1.steps() == 0 {
+                &self.tail
+            } else {
+                &other.tail
+            };
+            return if self.includes(p) && other.includes(p) {
+                Some(Intersection::Point(p.clone()))
+            } else {
+                None
+            };
+        }
+        let d = other.tail.minus(&self.tail);
+        let denominator = v1.cross(&v2);
+        if denominator == 0 {
+            if d.cross(&v1) != 0 {
+                return None;
+            }
+            // Collinear: compare the positions of the other's ends along self.
+            let (a, b) = (self.steps_to(&other.tail)?, self.steps_to(&other.head)?);
+            let low = cmp::max(0, cmp::min(a, b));
+            let high = cmp::min(self.length(), cmp::max(a, b));
+            return match low.cmp(&high) {
+                cmp::Ordering::Less => Some(Intersection::Overlap(LineSegment::new(
+                    self.point_at(low),
+                    self.point_at(high),
+                ))),
+                cmp::Ordering::Equal => Some(Intersection::Point(self.point_at(low))),
+                cmp::Ordering::Greater => None,
+            };
+        }
+        // self.tail + v1 * numerator / denominator is on other's line.
+        let numerator = d.cross(&v2);
+        let (x, y) = (i128::from(v1.x) * numerator, i128::from(v1.y) * numerator);
+        if x % denominator != 0 || y % denominator != 0 {
+            return None;
+        }
+        let coordinate = |start: i32, v: i128| i32::try_from(i128::from(start) + v / denominator);
+        let p = Point::new(
+            coordinate(self.tail.x, x).ok()?,
+            coordinate(self.tail.y, y).ok()?,
+        );
+        if self.includes(&p) && other.includes(&p) {
+            Some(Intersection::Point(p))
         } else {
             None
         }
     }
 
-    fn length(&self) -> i32 {
-        self.tail.distance_from_point(&self.head)
+    /// The number of steps between grid points from tail to head.
+    fn length(&self) -> i64 {
+        self.head.minus(&self.tail).steps()
     }
 
     /// returns true if point is on the line segment, false otherwise.
     fn includes(&self, p: &Point) -> bool {
-        match self.direction() {
-            Direction::Vertical => {
-                p.x == self.tail.x
-                    && p.y <= cmp::max(self.tail.y, self.head.y)
-                    && p.y >= cmp::min(self.tail.y, self.head.y)
-            }
-            Direction::Horizontal => {
-                p.y == self.tail.y
-                    && p.x <= cmp::max(self.tail.x, self.head.x)
-                    && p.x >= cmp::min(self.tail.x, self.head.x)
-            }
+        match self.steps_to(p) {
+            Some(steps) => steps >= 0 && steps <= self.length(),
+            None => false,
+        }
+    }
+
+    /// How many steps along the segment's line `p` is from the tail, counting
+    /// backwards from the tail as negative. `None` if `p` is not on the line.
+    fn steps_to(&self, p: &Point) -> Option<i64> {
+        let u = self.direction();
+        let d = p.minus(&self.tail);
+        if u.x == 0 && u.y == 0 {
+            return if d.steps() == 0 { Some(0) } else { None };
+        }
+        if d.cross(&u) != 0 {
+            return None;
         }
+        Some(if u.x != 0 { d.x / u.x } else { d.y / u.y })
+    }
+
+    /// The point `steps` steps from the tail, which must be on the segment.
+    fn point_at(&self, steps: i64) -> Point {
+        let u = self.direction();
+        Point::new(
+            (i64::from(self.tail.x) + u.x * steps) as i32,
+            (i64::from(self.tail.y) + u.y * steps) as i32,
+        )
     }
 
     fn new(tail: Point, head: Point) -> Self {
-        Self{tail, head}
+        Self { tail, head }
     }
 }
 
-enum Vector {
-    Right(i32),
-    Up(i32),
+/// Where two line segments meet.
+#[derive(Debug, PartialEq)]
+enum Intersection {
+    Point(Point),
+    /// The segments are collinear and share more than one point.
+    Overlap(LineSegment),
+}
+
+impl Intersection {
+    /// The point of the intersection closest to the origin, other than the
+    /// origin itself.
+    fn closest_to_origin(&self) -> Option<Point> {
+        let overlap = match self {
+            Intersection::Point(p) if p.distance_from_origin() == 0 => return None,
+            Intersection::Point(p) => return Some(p.clone()),
+            Intersection::Overlap(overlap) => overlap,
+        };
+        // The distance is convex along the overlap, so it is lowest at an end,
+        // where a coordinate changes sign, or next to the origin.
+        let (t, u) = (&overlap.tail, overlap.direction());
+        let mut candidates = vec![0, overlap.length()];
+        for &(start, step) in &[(i64::from(t.x), u.x), (i64::from(t.y), u.y)] {
+            if step != 0 {
+                let k = if step > 0 {
+                    (-start).div_euclid(step)
+                } else {
+                    start.div_euclid(-step)
+                };
+                candidates.extend(&[k, k + 1]);
+            }
+        }
+        if let Some(k) = overlap.steps_to(&Point::new(0, 0)) {
+            candidates.extend(&[k - 1, k + 1]);
+        }
+        candidates
+            .into_iter()
+            .filter(|&k| k >= 0 && k <= overlap.length())
+            .map(|k| overlap.point_at(k))
+            .filter(|p| p.distance_from_origin() != 0)
+            .min_by_key(Point::distance_from_origin)
+    }
+
+    /// The points of the intersection, other than the origin, where the
+    /// combined steps along two segments can be lowest. Along an overlap the
+    /// steps along each segment change linearly, so their sum is lowest at an
+    /// end, or next to the origin if that is an end.
+    fn step_candidates(&self) -> Vec<Point> {
+        let overlap = match self {
+            Intersection::Point(p) => return vec![p.clone()],
+            Intersection::Overlap(overlap) => overlap,
+        };
+        let last = overlap.length();
+        let ends = if overlap.tail.distance_from_origin() == 0 {
+            [1, last]
+        } else if overlap.head.distance_from_origin() == 0 {
+            [0, last - 1]
+        } else {
+            [0, last]
+        };
+        ends.iter().map(|&k| overlap.point_at(k)).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Vector {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl Vector {
-    fn from_string(s: &str) -> Vector {
-        let (dir, num) = s.split_at(1);
-        let magnitude = num.parse().unwrap();
-        match dir {
-            "R" => Vector::Right(magnitude),
-            "U" => Vector::Up(magnitude),
-            "L" => Vector::Right(-magnitude),
-            "D" => Vector::Up(-magnitude),
-            _ => panic!("Cannot handle direction {}", dir),
+    /// Parses a move: `R`, `L`, `U`, `D`, `NE`, `NW`, `SE` or `SW` followed by
+    /// a non-negative number of steps, or an arbitrary vector such as `X3Y-2`.
+    fn from_string(s: &str) -> Result<Vector, String> {
+        let error = || format!("`{}` is not a move", s);
+        if let Some(rest) = s.strip_prefix('X') {
+            let (x, y) = rest.split_once('Y').ok_or_else(error)?;
+            return match (x.parse::<i32>(), y.parse::<i32>()) {
+                (Ok(x), Ok(y)) => Ok(Vector::new(i64::from(x), i64::from(y))),
+                _ => Err(error()),
+            };
+        }
+        let split = s
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(s.len());
+        let (dir, num) = s.split_at(split);
+        let magnitude: i32 = num.parse().map_err(|_| error())?;
+        if magnitude < 0 {
+            return Err(error());
         }
+        let (x, y) = match dir {
+            "R" => (1, 0),
+            "U" => (0, 1),
+            "L" => (-1, 0),
+            "D" => (0, -1),
+            "NE" => (1, 1),
+            "NW" => (-1, 1),
+            "SE" => (1, -1),
+            "SW" => (-1, -1),
+            _ => return Err(error()),
+        };
+        let component = |unit: i64| unit.checked_mul(i64::from(magnitude)).ok_or_else(error);
+        Ok(Vector::new(component(x)?, component(y)?))
+    }
+
+    /// The number of steps between grid points along the vector, i.e. the
+    /// greatest common divisor of its components.
+    fn steps(&self) -> i64 {
+        let (mut a, mut b) = (self.x.unsigned_abs(), self.y.unsigned_abs());
+        while b != 0 {
+            let r = a % b;
+            a = b;
+            b = r;
+        }
+        a as i64
+    }
+
+    fn cross(&self, other: &Self) -> i128 {
+        i128::from(self.x) * i128::from(other.y) - i128::from(self.y) * i128::from(other.x)
+    }
+
+    fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
     }
 }
 
-/// Turn a string path into a vector of line segments
-fn parse_directions(path: &str) -> Vec<LineSegment> {
+/// Turn a string path into a vector of line segments. Errors name the index
+/// of the bad move.
+fn parse_directions(path: &str) -> Result<Vec<LineSegment>, String> {
     let mut result = Vec::new();
-    path.split(',')
-        .map(|s| Vector::from_string(s))
-        .fold(Point::new(0, 0), |last_point, v| {
-            let new_point = last_point.transform(&v);
-            let new_segment = LineSegment::new(last_point, new_point.clone());
-            result.push(new_segment);
-            new_point
-        });
-
-    result
+    let mut last_point = Point::new(0, 0);
+    for (i, step) in path.split(',').enumerate() {
+        let v = Vector::from_string(step).map_err(|error| format!("move {}: {}", i, error))?;
+        let new_point = last_point
+            .transform(&v)
+            .ok_or_else(|| format!("move {}: `{}` leaves the grid", i, step))?;
+        result.push(LineSegment::new(last_point, new_point.clone()));
+        last_point = new_point;
+    }
+    Ok(result)
 }
 
-fn parse_file(path_to_input: &str) -> Result<(Vec<LineSegment>, Vec<LineSegment>), Error> {
+/// Every non-empty line of the file is a wire. Bad moves are reported as
+/// `InvalidData` errors naming the wire and the move.
+fn parse_file(path_to_input: &str) -> Result<Vec<Vec<LineSegment>>, Error> {
     let file = File::open(path_to_input)?;
     let reader = BufReader::new(file);
-    let mut lineses = reader
-        .lines()
-        .take(2)
-        .map(|line| parse_directions(&line.unwrap()));
-    let lines1 = lineses.next().unwrap();
-    let lines2 = lineses.next().unwrap();
-    Ok((lines1, lines2))
+    let mut wires = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            let wire = parse_directions(line.trim()).map_err(|error| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("wire {}, {}", wires.len(), error),
+                )
+            })?;
+            wires.push(wire);
+        }
+    }
+    Ok(wires)
 }
 
-fn steps_to_point(lines: &Vec<LineSegment>, point: &Point) -> i32 {
+/// Every index pair `(i, j)` of `wires` with `i < j`.
+fn wire_pairs(wires: &[Vec<LineSegment>]) -> impl Iterator<Item = (usize, usize)> {
+    let n = wires.len();
+    (0..n).flat_map(move |i| (i + 1..n).map(move |j| (i, j)))
+}
+
+/// The steps along `lines` to the tail of each segment.
+fn cumulative_steps(lines: &[LineSegment]) -> Vec<i64> {
     let mut steps = 0;
-    for line in lines {
-        if line.includes(point) {
-            return steps + line.tail.distance_from_point(point);
-        } else {
+    lines
+        .iter()
+        .map(|line| {
+            let before = steps;
             steps += line.length();
-        }
-    }
-    steps
+            before
+        })
+        .collect()
 }
 
-fn closest_intersection_steps(lines1: &Vec<LineSegment>, lines2: &Vec<LineSegment>) -> Option<i32> {
-    let mut closest_total_steps = i32::max_value();
-    for line1 in lines1 {
-        for line2 in lines2 {
-            if let Some(intersection) = line1.intersection(&line2) {
-                if intersection != Point::new(0, 0) {
-                    let total_steps = steps_to_point(lines1, &intersection)
-                        + steps_to_point(lines2, &intersection);
-                    if total_steps < closest_total_steps {
-                        closest_total_steps = total_steps;
-                    }
-                }
-            }
-        }
-    }
+fn closest_intersection_steps(lines1: &[LineSegment], lines2: &[LineSegment]) -> Option<i64> {
+    let (steps1, steps2) = (cumulative_steps(lines1), cumulative_steps(lines2));
+    // A wire's first visit to a point has the fewest steps, so the lowest
+    // total over every pair of segments uses the first visit of each wire.
+    sweep::crossings(lines1, lines2)
+        .into_iter()
+        .flat_map(|crossing| {
+            let (line1, line2) = (&lines1[crossing.first], &lines2[crossing.second]);
+            let base = steps1[crossing.first] + steps2[crossing.second];
+            crossing
+                .intersection
+                .step_candidates()
+                .into_iter()
+                .filter(|point| *point != Point::new(0, 0))
+                .map(move |point| {
+                    base + line1.steps_to(&point).unwrap() + line2.steps_to(&point).unwrap()
+                })
+        })
+        .min()
+}
 
-    if closest_total_steps == i32::max_value() {
-        None
-    } else {
-        Some(closest_total_steps)
-    }
+/// The fewest combined steps to a crossing of any two wires, along with
+/// those wires.
+fn closest_pair_steps(wires: &[Vec<LineSegment>]) -> Option<(i64, (usize, usize))> {
+    wire_pairs(wires)
+        .filter_map(|(i, j)| {
+            closest_intersection_steps(&wires[i], &wires[j]).map(|steps| (steps, (i, j)))
+        })
+        .min()
 }
 
-pub fn pt2(path_to_input: &str) -> Result<Option<i32>, Error> {
-    let (lines1, lines2) = parse_file(path_to_input)?;
-    Ok(closest_intersection_steps(&lines1, &lines2))
+pub fn pt2(path_to_input: &str) -> Result<Option<i64>, Error> {
+    Ok(closest_pair(path_to_input)?.map(|pair| pair.steps))
 }
 
-fn closest_intersection_distance(
-    lines1: &Vec<LineSegment>,
-    lines2: &Vec<LineSegment>,
-) -> Option<i32> {
-    let mut closest_distance = i32::max_value();
-    for line1 in lines1 {
-        for line2 in lines2 {
-            if let Some(intersection) = line1.intersection(&line2) {
-                let distance = intersection.distance_from_origin();
-                if distance < closest_distance && distance != 0 {
-                    closest_distance = distance;
-                }
-            }
-        }
-    }
-    if closest_distance == i32::max_value() {
-        None
-    } else {
-        Some(closest_distance)
-    }
+/// The fewest combined steps to a crossing of two wires.
+#[derive(Debug, PartialEq)]
+pub struct ClosestPair {
+    pub steps: i64,
+    /// The indices of the two wires, in increasing order.
+    pub wires: (usize, usize),
+}
+
+/// The pair of wires in the file with the fewest combined steps to a crossing.
+pub fn closest_pair(path_to_input: &str) -> Result<Option<ClosestPair>, Error> {
+    Ok(closest_pair_steps(&parse_file(path_to_input)?)
+        .map(|(steps, wires)| ClosestPair { steps, wires }))
+}
+
+fn closest_intersection_distance(lines1: &[LineSegment], lines2: &[LineSegment]) -> Option<i64> {
+    sweep::crossings(lines1, lines2)
+        .into_iter()
+        .filter_map(|crossing| crossing.intersection.closest_to_origin())
+        .map(|point| point.distance_from_origin())
+        .min()
+}
+
+/// The distance of the closest crossing of any two wires.
+pub fn pt1(path_to_input: &str) -> Result<Option<i64>, Error> {
+    let wires = parse_file(path_to_input)?;
+    Ok(wire_pairs(&wires)
+        .filter_map(|(i, j)| closest_intersection_distance(&wires[i], &wires[j]))
+        .min())
+}
+
+/// Every place where at least two of the wires in the file cross.
+pub fn crossings(path_to_input: &str) -> Result<Vec<WireCrossing>, Error> {
+    Ok(wire_crossings(&parse_file(path_to_input)?))
 }
 
-pub fn pt1(path_to_input: &str) -> Result<Option<i32>, Error> {
-    let (lines1, lines2) = parse_file(path_to_input)?;
-    Ok(closest_intersection_distance(&lines1, &lines2))
+/// The distance of the closest point that every wire in the file crosses.
+pub fn closest_common_crossing(path_to_input: &str) -> Result<Option<i64>, Error> {
+    let wires = parse_file(path_to_input)?;
+    Ok(wire_crossings(&wires)
+        .into_iter()
+        .find(|crossing| crossing.wires.len() == wires.len())
+        .map(|crossing| crossing.place.closest_to_origin().distance_from_origin()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn parse_directions_test() {
         let path = "R75,D30,R83";
         assert_eq!(
-            parse_directions(path),
+            parse_directions(path).unwrap(),
             vec![
                 LineSegment::new(Point::new(0, 0), Point::new(75, 0)),
                 LineSegment::new(Point::new(75, 0), Point::new(75, -30)),
@@ -225,20 +437,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_diagonal_and_arbitrary_moves() {
+        assert_eq!(
+            parse_directions("NE3,SE1,SW2,NW1,X4Y-6").unwrap(),
+            vec![
+                LineSegment::new(Point::new(0, 0), Point::new(3, 3)),
+                LineSegment::new(Point::new(3, 3), Point::new(4, 2)),
+                LineSegment::new(Point::new(4, 2), Point::new(2, 0)),
+                LineSegment::new(Point::new(2, 0), Point::new(1, 1)),
+                LineSegment::new(Point::new(1, 1), Point::new(5, -5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_moves_are_errors() {
+        assert_eq!(
+            parse_directions("R1,Q5").unwrap_err(),
+            "move 1: `Q5` is not a move"
+        );
+        assert_eq!(
+            parse_directions("R").unwrap_err(),
+            "move 0: `R` is not a move"
+        );
+        assert_eq!(
+            parse_directions("U2,X1Yz").unwrap_err(),
+            "move 1: `X1Yz` is not a move"
+        );
+        assert_eq!(
+            parse_directions("X1").unwrap_err(),
+            "move 0: `X1` is not a move"
+        );
+        assert_eq!(
+            parse_directions("R-5").unwrap_err(),
+            "move 0: `R-5` is not a move"
+        );
+        assert_eq!(
+            parse_directions("L-2147483648").unwrap_err(),
+            "move 0: `L-2147483648` is not a move"
+        );
+        assert_eq!(
+            parse_directions("R2000000000,R2000000000").unwrap_err(),
+            "move 1: `R2000000000` leaves the grid"
+        );
+        let path =
+            std::env::temp_dir().join(format!("day3_bad_moves_are_errors_{}", std::process::id()));
+        fs::write(&path, "R8,U5\n\nU7,R6,D4x\n").unwrap();
+        let error = pt1(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "wire 1, move 2: `D4x` is not a move");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn longest_moves_stay_on_the_grid() {
+        let lines = parse_directions("X-2147483648Y0,R2147483647").unwrap();
+        assert_eq!(lines[0].head, Point::new(i32::MIN, 0));
+        assert_eq!(lines[0].length(), 1 << 31);
+        assert_eq!(lines[1].head, Point::new(-1, 0));
+        assert_eq!(
+            parse_directions("L2147483647,L2").unwrap_err(),
+            "move 1: `L2` leaves the grid"
+        );
+    }
+
     #[test]
     fn direction_test() {
         let horizontal_line = LineSegment::new(Point::new(-1, 1), Point::new(5, 1));
-        assert_eq!(Direction::Horizontal, horizontal_line.direction());
+        assert_eq!(Vector::new(1, 0), horizontal_line.direction());
 
         let vertical_line = LineSegment::new(Point::new(-1, 1), Point::new(-1, -7));
-        assert_eq!(Direction::Vertical, vertical_line.direction());
-    }
+        assert_eq!(Vector::new(0, -1), vertical_line.direction());
 
-    #[test]
-    #[should_panic]
-    fn diagonal_direction_test() {
-        let diagonal_line = LineSegment::new(Point::new(-1, 1), Point::new(5, -7));
-        diagonal_line.direction();
+        let diagonal_line = LineSegment::new(Point::new(-1, 1), Point::new(5, -11));
+        assert_eq!(Vector::new(1, -2), diagonal_line.direction());
+        assert_eq!(6, diagonal_line.length());
     }
 
     #[test]
@@ -247,27 +521,183 @@ mod tests {
         let another_horizontal_line = LineSegment::new(Point::new(-1, 2), Point::new(5, 2));
         let vertical_line = LineSegment::new(Point::new(-1, 1), Point::new(-1, -7));
         assert_eq!(
-            Some(Point::new(-1, 1)),
+            Some(Intersection::Point(Point::new(-1, 1))),
             horizontal_line.intersection(&vertical_line)
         );
         assert_eq!(
-            Some(Point::new(-1, 1)),
+            Some(Intersection::Point(Point::new(-1, 1))),
             vertical_line.intersection(&horizontal_line)
         );
         assert_eq!(None, horizontal_line.intersection(&another_horizontal_line));
     }
 
+    #[test]
+    fn diagonal_intersection_test() {
+        let up = LineSegment::new(Point::new(0, 0), Point::new(4, 4));
+        let down = LineSegment::new(Point::new(0, 4), Point::new(4, 0));
+        assert_eq!(
+            Some(Intersection::Point(Point::new(2, 2))),
+            up.intersection(&down)
+        );
+        assert_eq!(
+            Some(Intersection::Point(Point::new(2, 2))),
+            down.intersection(&up)
+        );
+
+        // These cross at (1.5, 1.5), between grid points.
+        let down = LineSegment::new(Point::new(0, 3), Point::new(3, 0));
+        assert_eq!(None, up.intersection(&down));
+
+        let steep = LineSegment::new(Point::new(1, -1), Point::new(4, 8));
+        assert_eq!(
+            Some(Intersection::Point(Point::new(2, 2))),
+            up.intersection(&steep)
+        );
+        let horizontal = LineSegment::new(Point::new(-5, 3), Point::new(5, 3));
+        assert_eq!(
+            Some(Intersection::Point(Point::new(3, 3))),
+            up.intersection(&horizontal)
+        );
+        let short = LineSegment::new(Point::new(3, 0), Point::new(3, 2));
+        assert_eq!(None, up.intersection(&short));
+    }
+
+    #[test]
+    fn long_segments_intersect() {
+        let up = LineSegment::new(
+            Point::new(-1_000_000_000, -1_000_000_000),
+            Point::new(1_000_000_000, 1_000_000_000),
+        );
+        let down = LineSegment::new(
+            Point::new(-1_000_000_000, 1_000_000_000),
+            Point::new(1_000_000_000, -1_000_000_000),
+        );
+        assert_eq!(
+            Some(Intersection::Point(Point::new(0, 0))),
+            up.intersection(&down)
+        );
+        let far = LineSegment::new(Point::new(1_000_000_000, 0), Point::new(1_000_000_001, 2));
+        assert_eq!(None, up.intersection(&far));
+    }
+
+    #[test]
+    fn collinear_intersection_test() {
+        let line = LineSegment::new(Point::new(0, 0), Point::new(4, 2));
+        let touching = LineSegment::new(Point::new(8, 4), Point::new(4, 2));
+        assert_eq!(
+            Some(Intersection::Point(Point::new(4, 2))),
+            line.intersection(&touching)
+        );
+        let apart = LineSegment::new(Point::new(6, 3), Point::new(8, 4));
+        assert_eq!(None, line.intersection(&apart));
+        let parallel = LineSegment::new(Point::new(0, 1), Point::new(4, 3));
+        assert_eq!(None, line.intersection(&parallel));
+
+        let point = LineSegment::new(Point::new(2, 1), Point::new(2, 1));
+        assert_eq!(
+            Some(Intersection::Point(Point::new(2, 1))),
+            line.intersection(&point)
+        );
+        assert_eq!(
+            Some(Intersection::Point(Point::new(2, 1))),
+            point.intersection(&line)
+        );
+        let off = LineSegment::new(Point::new(1, 1), Point::new(1, 1));
+        assert_eq!(None, line.intersection(&off));
+    }
+
+    #[test]
+    fn overlap_test() {
+        let line = LineSegment::new(Point::new(0, 0), Point::new(4, 2));
+        let longer = LineSegment::new(Point::new(6, 3), Point::new(-2, -1));
+        assert_eq!(
+            Some(Intersection::Overlap(LineSegment::new(
+                Point::new(0, 0),
+                Point::new(4, 2)
+            ))),
+            line.intersection(&longer)
+        );
+        assert_eq!(
+            Some(Intersection::Overlap(LineSegment::new(
+                Point::new(4, 2),
+                Point::new(0, 0)
+            ))),
+            longer.intersection(&line)
+        );
+        let partial = LineSegment::new(Point::new(2, 1), Point::new(8, 4));
+        assert_eq!(
+            Some(Intersection::Overlap(LineSegment::new(
+                Point::new(2, 1),
+                Point::new(4, 2)
+            ))),
+            line.intersection(&partial)
+        );
+    }
+
+    #[test]
+    fn closest_point_of_overlap_test() {
+        let closest = |tail, head| {
+            Intersection::Overlap(LineSegment::new(tail, head))
+                .closest_to_origin()
+                .map(|p| p.distance_from_origin())
+        };
+        assert_eq!(Some(2), closest(Point::new(-3, 5), Point::new(4, -2)));
+        assert_eq!(Some(1), closest(Point::new(-2, 0), Point::new(3, 0)));
+        assert_eq!(Some(1), closest(Point::new(0, 0), Point::new(0, 3)));
+        assert_eq!(Some(7), closest(Point::new(9, -2), Point::new(3, 4)));
+        assert_eq!(
+            None,
+            Intersection::Point(Point::new(0, 0)).closest_to_origin()
+        );
+
+        let overlap = Intersection::Overlap(LineSegment::new(Point::new(0, 0), Point::new(5, 0)));
+        assert_eq!(
+            overlap.step_candidates(),
+            vec![Point::new(1, 0), Point::new(5, 0)]
+        );
+    }
+
+    #[test]
+    fn overlapping_wires_test() {
+        // The wires share the x axis from (-2, 0) to (6, 0).
+        let lines1 = parse_directions("R10,U3").unwrap();
+        let lines2 = parse_directions("L2,R8,D3").unwrap();
+        assert_eq!(closest_intersection_distance(&lines1, &lines2), Some(1));
+        // (1, 0) takes 1 + 5 steps.
+        assert_eq!(closest_intersection_steps(&lines1, &lines2), Some(6));
+    }
+
+    #[test]
+    fn includes_test() {
+        let line = LineSegment::new(Point::new(1, 1), Point::new(7, -8));
+        assert!(line.includes(&Point::new(1, 1)));
+        assert!(line.includes(&Point::new(5, -5)));
+        assert!(line.includes(&Point::new(7, -8)));
+        assert!(!line.includes(&Point::new(9, -11)));
+        assert!(!line.includes(&Point::new(2, 0)));
+        assert_eq!(Some(2), line.steps_to(&Point::new(5, -5)));
+    }
+
+    #[test]
+    fn diagonal_wires_test() {
+        let lines1 = parse_directions("NE5,SE5").unwrap();
+        let lines2 = parse_directions("R10,NW3,X-4Y-2").unwrap();
+        assert_eq!(closest_intersection_distance(&lines1, &lines2), Some(10));
+        // (10, 0) takes 10 + 10 steps, (7, 3) takes 7 + 13.
+        assert_eq!(closest_intersection_steps(&lines1, &lines2), Some(20));
+    }
+
     #[test]
     fn closest_intersection_test() {
-        let lines1 = parse_directions("R75,D30,R83,U83,L12,D49,R71,U7,L72");
-        let lines2 = parse_directions("U62,R66,U55,R34,D71,R55,D58,R83");
+        let lines1 = parse_directions("R75,D30,R83,U83,L12,D49,R71,U7,L72").unwrap();
+        let lines2 = parse_directions("U62,R66,U55,R34,D71,R55,D58,R83").unwrap();
         assert_eq!(closest_intersection_distance(&lines1, &lines2), Some(159));
     }
 
     #[test]
     fn closest_intersection_test2() {
-        let lines1 = parse_directions("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51");
-        let lines2 = parse_directions("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7");
+        let lines1 = parse_directions("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51").unwrap();
+        let lines2 = parse_directions("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7").unwrap();
         assert_eq!(closest_intersection_distance(&lines1, &lines2), Some(135));
     }
 
@@ -278,20 +708,109 @@ mod tests {
 
     #[test]
     fn closest_intersection_steps_test() {
-        let lines1 = parse_directions("R75,D30,R83,U83,L12,D49,R71,U7,L72");
-        let lines2 = parse_directions("U62,R66,U55,R34,D71,R55,D58,R83");
+        let lines1 = parse_directions("R75,D30,R83,U83,L12,D49,R71,U7,L72").unwrap();
+        let lines2 = parse_directions("U62,R66,U55,R34,D71,R55,D58,R83").unwrap();
         assert_eq!(closest_intersection_steps(&lines1, &lines2), Some(610));
     }
 
     #[test]
     fn closest_intersection_steps_test2() {
-        let lines1 = parse_directions("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51");
-        let lines2 = parse_directions("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7");
+        let lines1 = parse_directions("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51").unwrap();
+        let lines2 = parse_directions("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7").unwrap();
         assert_eq!(closest_intersection_steps(&lines1, &lines2), Some(410));
     }
 
+    #[test]
+    fn long_moves_test() {
+        let lines1 =
+            parse_directions("R1000000000,U2,L1000000000,U2,R1000000000,U2,L1000000000").unwrap();
+        let lines2 = parse_directions("U7,R1").unwrap();
+        assert_eq!(cumulative_steps(&lines1)[6], 3_000_000_006);
+        assert_eq!(
+            closest_intersection_steps(&lines1, &lines2),
+            Some(2_000_000_004)
+        );
+        assert_eq!(closest_intersection_distance(&lines1, &lines2), Some(2));
+    }
+
+    #[test]
+    fn three_wires_test() {
+        let wires = vec![
+            parse_directions("R10,U10").unwrap(),
+            parse_directions("U5,R10").unwrap(),
+            // Runs along wire 0 from (10, 0) to (10, 5), where wire 1 ends.
+            parse_directions("L1,U4,R4,D5,R7,U6,NW2").unwrap(),
+        ];
+        let crossings = wire_crossings(&wires);
+        assert_eq!(
+            crossings[0],
+            WireCrossing {
+                place: Place::Point(Point::new(3, 0)),
+                wires: vec![0, 2],
+            }
+        );
+        assert!(crossings.contains(&WireCrossing {
+            place: Place::Run {
+                from: Point::new(10, 0),
+                to: Point::new(10, 4),
+            },
+            wires: vec![0, 2],
+        }));
+        let common: Vec<&WireCrossing> = crossings
+            .iter()
+            .filter(|crossing| crossing.wires.len() == 3)
+            .collect();
+        assert_eq!(common.len(), 1);
+        assert_eq!(common[0].place, Place::Point(Point::new(10, 5)));
+        // (0, 4) takes 4 + 6 steps.
+        assert_eq!(closest_pair_steps(&wires), Some((10, (1, 2))));
+    }
+
+    #[test]
+    fn overlaps_are_runs_of_crossings() {
+        let wires = vec![
+            parse_directions("R4").unwrap(),
+            parse_directions("R2,U1").unwrap(),
+        ];
+        let places: Vec<Place> = wire_crossings(&wires)
+            .into_iter()
+            .map(|crossing| crossing.place)
+            .collect();
+        assert_eq!(
+            places,
+            vec![Place::Run {
+                from: Point::new(1, 0),
+                to: Point::new(2, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn input_has_two_wires() {
+        assert_eq!(parse_file("input").unwrap().len(), 2);
+        let crossings = crossings("input").unwrap();
+        assert_eq!(
+            crossings[0]
+                .place
+                .closest_to_origin()
+                .distance_from_origin(),
+            1519
+        );
+        assert!(crossings
+            .iter()
+            .all(|crossing| crossing.wires == vec![0, 1]));
+        assert_eq!(closest_common_crossing("input").unwrap(), Some(1519));
+    }
+
     #[test]
     fn pt2_test() {
         assert_eq!(pt2("input").unwrap().unwrap(), 14358);
+        assert_eq!(
+            closest_pair("input").unwrap(),
+            Some(ClosestPair {
+                steps: 14358,
+                wires: (0, 1),
+            })
+        );
     }
 }
@@ -0,0 +1,347 @@
+//! Where any of several wires cross, without visiting every grid point.
+//!
+//! Collinear segments of two wires can share a billion points, so overlaps
+//! stay runs. Runs on the same line are cut wherever the set of wires on them
+//! changes, and around every point where more wires meet them, which is
+//! reported on its own with every wire there.
+
+use crate::{sweep, wire_pairs, Intersection, LineSegment, Point, Vector};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+/// Where wires cross: a single grid point, or every grid point of a straight
+/// run from `from` to `to`, both included.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Place {
+    Point(Point),
+    Run { from: Point, to: Point },
+}
+
+impl Place {
+    /// The point of the place closest to the origin.
+    pub fn closest_to_origin(&self) -> Point {
+        match self {
+            Place::Point(p) => p.clone(),
+            // Runs never include the origin.
+            Place::Run { from, to } => {
+                Intersection::Overlap(LineSegment::new(from.clone(), to.clone()))
+                    .closest_to_origin()
+                    .unwrap()
+            }
+        }
+    }
+
+    /// The number of grid points of the place.
+    pub fn point_count(&self) -> u64 {
+        match self {
+            Place::Point(_) => 1,
+            Place::Run { from, to } => to.minus(from).steps() as u64 + 1,
+        }
+    }
+
+    /// Whether `p` is one of the grid points of the place.
+    pub fn contains(&self, p: &Point) -> bool {
+        match self {
+            Place::Point(q) => q == p,
+            Place::Run { from, to } => LineSegment::new(from.clone(), to.clone()).includes(p),
+        }
+    }
+}
+
+/// A place, other than the origin, where at least two wires cross.
+#[derive(Debug, PartialEq)]
+pub struct WireCrossing {
+    pub place: Place,
+    /// The indices of the wires at the place, in increasing order.
+    pub wires: Vec<usize>,
+}
+
+/// The runs of crossings along one line, as ranges of steps from `through`.
+struct Line {
+    /// The step between grid points, with its first non-zero component
+    /// positive so that both ways along the line agree.
+    unit: Vector,
+    through: Point,
+    runs: Vec<(i64, i64, usize)>,
+}
+
+impl Line {
+    fn new(overlap: &LineSegment) -> Self {
+        let u = overlap.direction();
+        let unit = if u.x < 0 || (u.x == 0 && u.y < 0) {
+            Vector::new(-u.x, -u.y)
+        } else {
+            u
+        };
+        Line {
+            unit,
+            through: overlap.tail.clone(),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Identifies the line: its direction and `cross(p, unit)` for any of its
+    /// points `p`.
+    fn key(&self) -> (i64, i64, i128) {
+        let offset = self.through.minus(&Point::new(0, 0)).cross(&self.unit);
+        (self.unit.x, self.unit.y, offset)
+    }
+
+    /// How many steps `p` is from `through`, if it is on the line.
+    fn steps_to(&self, p: &Point) -> Option<i64> {
+        let d = p.minus(&self.through);
+        if d.cross(&self.unit) != 0 {
+            return None;
+        }
+        let along = |v: &Vector| {
+            i128::from(v.x) * i128::from(self.unit.x) + i128::from(v.y) * i128::from(self.unit.y)
+        };
+        i64::try_from(along(&d) / along(&self.unit)).ok()
+    }
+
+    /// The grid point `steps` steps from `through`, which must be on the grid.
+    fn point_at(&self, steps: i64) -> Point {
+        Point::new(
+            (i64::from(self.through.x) + self.unit.x * steps) as i32,
+            (i64::from(self.through.y) + self.unit.y * steps) as i32,
+        )
+    }
+
+    fn add_run(&mut self, overlap: &LineSegment, wires: &[usize]) {
+        let (a, b) = (
+            self.steps_to(&overlap.tail).unwrap(),
+            self.steps_to(&overlap.head).unwrap(),
+        );
+        for &wire in wires {
+            self.runs.push((a.min(b), a.max(b), wire));
+        }
+    }
+
+    fn segment(&self, &(from, to, _): &(i64, i64, usize)) -> LineSegment {
+        LineSegment::new(self.point_at(from), self.point_at(to))
+    }
+
+    /// The wires of the runs that include `p`.
+    fn wires_at<'a>(&'a self, p: &Point) -> impl Iterator<Item = usize> + 'a {
+        let steps = self.steps_to(p);
+        self.runs
+            .iter()
+            .filter(move |&&(from, to, _)| steps.is_some_and(|k| from <= k && k <= to))
+            .map(|&(_, _, wire)| wire)
+    }
+
+    /// The runs cut where their set of wires changes and around `cuts`, with
+    /// the wires along each.
+    fn pieces(&self, cuts: &BTreeSet<i64>) -> Vec<(i64, i64, BTreeSet<usize>)> {
+        let mut bounds = BTreeSet::new();
+        for &(from, to, _) in &self.runs {
+            bounds.insert(from);
+            bounds.insert(to + 1);
+        }
+        for &cut in cuts {
+            bounds.insert(cut);
+            bounds.insert(cut + 1);
+        }
+        let bounds: Vec<i64> = bounds.into_iter().collect();
+        let mut pieces: Vec<(i64, i64, BTreeSet<usize>)> = Vec::new();
+        for pair in bounds.windows(2) {
+            let (from, to) = (pair[0], pair[1] - 1);
+            if cuts.contains(&from) {
+                continue;
+            }
+            let wires: BTreeSet<usize> = self
+                .runs
+                .iter()
+                .filter(|&&(low, high, _)| low <= from && from <= high)
+                .map(|&(_, _, wire)| wire)
+                .collect();
+            if wires.is_empty() {
+                continue;
+            }
+            match pieces.last_mut() {
+                Some(last) if last.1 + 1 == from && last.2 == wires => last.1 = to,
+                _ => pieces.push((from, to, wires)),
+            }
+        }
+        pieces
+    }
+}
+
+/// Every place where at least two wires cross, ordered by the distance of
+/// its closest point from the origin and then by the position of that point.
+pub(crate) fn wire_crossings(wires: &[Vec<LineSegment>]) -> Vec<WireCrossing> {
+    let mut points: BTreeMap<(i32, i32), BTreeSet<usize>> = BTreeMap::new();
+    let mut lines: BTreeMap<(i64, i64, i128), Line> = BTreeMap::new();
+    for (i, j) in wire_pairs(wires) {
+        for crossing in sweep::crossings(&wires[i], &wires[j]) {
+            match crossing.intersection {
+                Intersection::Point(p) => {
+                    points.entry((p.x, p.y)).or_default().extend(&[i, j]);
+                }
+                Intersection::Overlap(overlap) => {
+                    let line = Line::new(&overlap);
+                    lines
+                        .entry(line.key())
+                        .or_insert(line)
+                        .add_run(&overlap, &[i, j]);
+                }
+            }
+        }
+    }
+    // Where runs on two lines meet, the wires of both are there.
+    let lines: Vec<Line> = lines.into_values().collect();
+    for (a, first) in lines.iter().enumerate() {
+        for second in &lines[a + 1..] {
+            for run1 in &first.runs {
+                for run2 in &second.runs {
+                    let meeting = first.segment(run1).intersection(&second.segment(run2));
+                    if let Some(Intersection::Point(p)) = meeting {
+                        points.entry((p.x, p.y)).or_default();
+                    }
+                }
+            }
+        }
+    }
+    points.remove(&(0, 0));
+
+    // A point stays part of the first run through it that has every wire
+    // there, and is cut out of every other run.
+    let mut cuts = vec![BTreeSet::new(); lines.len()];
+    let mut crossings = Vec::new();
+    for ((x, y), mut wires) in points {
+        let p = Point::new(x, y);
+        let mut on: Vec<(usize, i64, BTreeSet<usize>)> = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let along: BTreeSet<usize> = line.wires_at(&p).collect();
+            if !along.is_empty() {
+                wires.extend(&along);
+                on.push((index, line.steps_to(&p).unwrap(), along));
+            }
+        }
+        let kept = on.iter().position(|(_, _, along)| *along == wires);
+        for (position, (index, steps, _)) in on.into_iter().enumerate() {
+            if Some(position) != kept {
+                cuts[index].insert(steps);
+            }
+        }
+        if kept.is_none() {
+            crossings.push(WireCrossing {
+                place: Place::Point(p),
+                wires: wires.into_iter().collect(),
+            });
+        }
+    }
+    for (line, mut cuts) in lines.iter().zip(cuts) {
+        if let Some(origin) = line.steps_to(&Point::new(0, 0)) {
+            cuts.insert(origin);
+        }
+        for (from, to, wires) in line.pieces(&cuts) {
+            let place = if from == to {
+                Place::Point(line.point_at(from))
+            } else {
+                Place::Run {
+                    from: line.point_at(from),
+                    to: line.point_at(to),
+                }
+            };
+            crossings.push(WireCrossing {
+                place,
+                wires: wires.into_iter().collect(),
+            });
+        }
+    }
+    crossings.sort_by_cached_key(|crossing| {
+        let p = crossing.place.closest_to_origin();
+        (p.distance_from_origin(), p.x, p.y)
+    });
+    crossings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_directions;
+    use crate::sweep::tests::{random_wire, Rng};
+
+    /// Every grid point where at least two wires cross, found one point at a
+    /// time.
+    fn point_by_point(wires: &[Vec<LineSegment>]) -> BTreeMap<(i32, i32), Vec<usize>> {
+        let mut points: BTreeMap<(i32, i32), BTreeSet<usize>> = BTreeMap::new();
+        for (i, j) in wire_pairs(wires) {
+            for crossing in sweep::crossings(&wires[i], &wires[j]) {
+                let overlap = match crossing.intersection {
+                    Intersection::Point(p) => LineSegment::new(p.clone(), p),
+                    Intersection::Overlap(overlap) => overlap,
+                };
+                for k in 0..=overlap.length() {
+                    let p = overlap.point_at(k);
+                    points.entry((p.x, p.y)).or_default().extend(&[i, j]);
+                }
+            }
+        }
+        points.remove(&(0, 0));
+        points
+            .into_iter()
+            .map(|(p, wires)| (p, wires.into_iter().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_point_by_point() {
+        let mut rng = Rng(0x0bad_cafe);
+        let moves = ["R", "U", "L", "D", "NE", "SW", "X"];
+        for round in 0..200 {
+            let wires: Vec<Vec<LineSegment>> = (0..2 + round % 3)
+                .map(|_| parse_directions(&random_wire(&mut rng, 8, 6, &moves)).unwrap())
+                .collect();
+            let mut expanded = BTreeMap::new();
+            for crossing in wire_crossings(&wires) {
+                let run = match crossing.place {
+                    Place::Point(p) => LineSegment::new(p.clone(), p),
+                    Place::Run { from, to } => LineSegment::new(from, to),
+                };
+                for k in 0..=run.length() {
+                    let p = run.point_at(k);
+                    let previous = expanded.insert((p.x, p.y), crossing.wires.clone());
+                    assert!(previous.is_none(), "{:?} is in two places", p);
+                }
+            }
+            assert_eq!(expanded, point_by_point(&wires), "{:?}", wires);
+        }
+    }
+
+    #[test]
+    fn long_overlaps_stay_runs() {
+        let wires = vec![
+            parse_directions("R1000000000").unwrap(),
+            parse_directions("R1000000000,U1").unwrap(),
+            parse_directions("U1,R3,D2").unwrap(),
+        ];
+        let crossings = wire_crossings(&wires);
+        assert_eq!(
+            crossings,
+            vec![
+                WireCrossing {
+                    place: Place::Run {
+                        from: Point::new(1, 0),
+                        to: Point::new(2, 0),
+                    },
+                    wires: vec![0, 1],
+                },
+                WireCrossing {
+                    place: Place::Point(Point::new(3, 0)),
+                    wires: vec![0, 1, 2],
+                },
+                WireCrossing {
+                    place: Place::Run {
+                        from: Point::new(4, 0),
+                        to: Point::new(1_000_000_000, 0),
+                    },
+                    wires: vec![0, 1],
+                },
+            ]
+        );
+        assert_eq!(crossings[2].place.point_count(), 999_999_997);
+        assert!(crossings[2].place.contains(&Point::new(500, 0)));
+    }
+}
@@ -0,0 +1,295 @@
+//! Finds every pair of intersecting segments of two wires without comparing
+//! every segment with every other one.
+//!
+//! Segments are grouped by direction. For two directions `u` and `w`, the
+//! coordinates `cross(p, w)` and `cross(p, u)` turn segments along `u` into
+//! horizontal ones and segments along `w` into vertical ones, so a sweep over
+//! the first coordinate with a sorted set of active horizontals finds every
+//! crossing in O((n + k) log n). Segments with the same direction can only
+//! meet if they are on the same line, where overlapping ranges are found by a
+//! sweep along the line. The exact intersection of each pair is then left to
+//! `LineSegment::intersection`, which also drops crossings between grid points.
+//!
+//! Every direction of one wire is paired with every direction of the other, so
+//! wires with many different directions take longer. Wires that only move
+//! along the axes and diagonals have at most four directions each.
+
+use crate::{Intersection, LineSegment, Point, Vector};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Segment `first` of the first wire meets segment `second` of the second.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Crossing {
+    pub first: usize,
+    pub second: usize,
+    pub intersection: Intersection,
+}
+
+/// Every intersection between a segment of `lines1` and one of `lines2`,
+/// ordered by segment.
+pub(crate) fn crossings(lines1: &[LineSegment], lines2: &[LineSegment]) -> Vec<Crossing> {
+    let (groups1, groups2) = (by_direction(lines1), by_direction(lines2));
+    let mut pairs = Vec::new();
+    for (u, first) in &groups1 {
+        for (w, second) in &groups2 {
+            if u == w {
+                overlapping_pairs(lines1, first, lines2, second, u, &mut pairs);
+            } else {
+                crossing_pairs(lines1, first, lines2, second, u, w, &mut pairs);
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+        .into_iter()
+        .filter_map(|(first, second)| {
+            lines1[first]
+                .intersection(&lines2[second])
+                .map(|intersection| Crossing {
+                    first,
+                    second,
+                    intersection,
+                })
+        })
+        .collect()
+}
+
+/// The indices of `lines`, grouped by their direction up to sign. Zero-length
+/// segments go with the horizontal ones.
+fn by_direction(lines: &[LineSegment]) -> BTreeMap<(i64, i64), Vec<usize>> {
+    let mut groups: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        let u = line.direction();
+        let key = if u.x > 0 || (u.x == 0 && u.y > 0) {
+            (u.x, u.y)
+        } else if u.x == 0 && u.y == 0 {
+            (1, 0)
+        } else {
+            (-u.x, -u.y)
+        };
+        groups.entry(key).or_default().push(i);
+    }
+    groups
+}
+
+fn cross(p: &Point, (x, y): &(i64, i64)) -> i128 {
+    Vector::new(i64::from(p.x), i64::from(p.y)).cross(&Vector::new(*x, *y))
+}
+
+/// The range of `cross(p, w)` over the points `p` of `line`.
+fn span(line: &LineSegment, w: &(i64, i64)) -> (i128, i128) {
+    let (a, b) = (cross(&line.tail, w), cross(&line.head, w));
+    (a.min(b), a.max(b))
+}
+
+/// Pairs of segments along `u` from `lines1` and along `w` from `lines2` that
+/// may cross.
+fn crossing_pairs(
+    lines1: &[LineSegment],
+    first: &[usize],
+    lines2: &[LineSegment],
+    second: &[usize],
+    u: &(i64, i64),
+    w: &(i64, i64),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    // Inserts come before queries and removals after them at the same
+    // coordinate, since segments include their ends.
+    const INSERT: u8 = 0;
+    const QUERY: u8 = 1;
+    const REMOVE: u8 = 2;
+    let mut events = Vec::with_capacity(2 * first.len() + second.len());
+    for &i in first {
+        let (low, high) = span(&lines1[i], w);
+        events.push((low, INSERT, i));
+        events.push((high, REMOVE, i));
+    }
+    for &j in second {
+        events.push((cross(&lines2[j].tail, w), QUERY, j));
+    }
+    events.sort_unstable();
+
+    let mut active = BTreeSet::new();
+    for (_, kind, index) in events {
+        match kind {
+            INSERT => {
+                active.insert((cross(&lines1[index].tail, u), index));
+            }
+            REMOVE => {
+                active.remove(&(cross(&lines1[index].tail, u), index));
+            }
+            _ => {
+                let (low, high) = span(&lines2[index], u);
+                for &(_, i) in active.range((low, 0)..=(high, usize::MAX)) {
+                    pairs.push((i, index));
+                }
+            }
+        }
+    }
+}
+
+/// Pairs of segments along `u` from `lines1` and `lines2` that are on the same
+/// line and share at least one point.
+fn overlapping_pairs(
+    lines1: &[LineSegment],
+    first: &[usize],
+    lines2: &[LineSegment],
+    second: &[usize],
+    u: &(i64, i64),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    // Positions along the line, which grow in the direction of `u`.
+    let along = |p: &Point| i128::from(p.x) * i128::from(u.0) + i128::from(p.y) * i128::from(u.1);
+    let mut intervals = Vec::with_capacity(first.len() + second.len());
+    for (wire, lines, indices) in [(0, lines1, first), (1, lines2, second)] {
+        for &i in indices {
+            let line = &lines[i];
+            let (a, b) = (along(&line.tail), along(&line.head));
+            intervals.push((cross(&line.tail, u), a.min(b), a.max(b), wire, i));
+        }
+    }
+    intervals.sort_unstable();
+
+    // The segments of each wire on the current line that have not ended yet,
+    // by where they end.
+    let mut active = [BTreeSet::new(), BTreeSet::new()];
+    let mut current_line = None;
+    for (line, start, end, wire, i) in intervals {
+        if current_line != Some(line) {
+            current_line = Some(line);
+            active[0].clear();
+            active[1].clear();
+        }
+        for set in active.iter_mut() {
+            while set.first().is_some_and(|&(ended, _)| ended < start) {
+                set.pop_first();
+            }
+        }
+        for &(_, j) in &active[1 - wire] {
+            pairs.push(if wire == 0 { (i, j) } else { (j, i) });
+        }
+        active[wire].insert((end, i));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parse_directions;
+    use std::time::Instant;
+
+    /// xorshift64, for reproducible wires.
+    pub(crate) struct Rng(pub u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(crate) fn below(&mut self, n: u64) -> i32 {
+            (self.next() % n) as i32
+        }
+    }
+
+    /// A random wire of `len` moves of up to `max` steps, in the puzzle's
+    /// format. `moves` lists the directions to choose from.
+    pub(crate) fn random_wire(rng: &mut Rng, len: usize, max: u64, moves: &[&str]) -> String {
+        (0..len)
+            .map(|_| {
+                let m = moves[rng.below(moves.len() as u64) as usize];
+                if m == "X" {
+                    format!("X{}Y{}", rng.below(7) - 3, rng.below(7) - 3)
+                } else {
+                    format!("{}{}", m, rng.below(max) + 1)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn brute_force(lines1: &[LineSegment], lines2: &[LineSegment]) -> Vec<Crossing> {
+        let mut crossings = Vec::new();
+        for (first, line1) in lines1.iter().enumerate() {
+            for (second, line2) in lines2.iter().enumerate() {
+                if let Some(intersection) = line1.intersection(line2) {
+                    crossings.push(Crossing {
+                        first,
+                        second,
+                        intersection,
+                    });
+                }
+            }
+        }
+        crossings
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng(0x5eed_1234);
+        let move_sets: [&[&str]; 3] = [
+            &["R", "L", "U", "D"],
+            &["R", "L", "U", "D", "NE", "NW", "SE", "SW"],
+            &["R", "U", "NE", "SW", "X"],
+        ];
+        for round in 0..60 {
+            let moves = move_sets[round % 3];
+            let lines1 = parse_directions(&random_wire(&mut rng, 60, 12, moves)).unwrap();
+            let lines2 = parse_directions(&random_wire(&mut rng, 60, 12, moves)).unwrap();
+            assert_eq!(crossings(&lines1, &lines2), brute_force(&lines1, &lines2));
+        }
+    }
+
+    #[test]
+    fn finds_overlaps_and_zero_length_segments() {
+        let lines1 = parse_directions("R10,U0,U3,L4,D3").unwrap();
+        let lines2 = parse_directions("L2,R8,D3,R0,U3,NE1").unwrap();
+        assert_eq!(crossings(&lines1, &lines2), brute_force(&lines1, &lines2));
+        assert!(crossings(&lines1, &lines2)
+            .iter()
+            .any(|crossing| matches!(crossing.intersection, Intersection::Overlap(_))));
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_100k_segments() {
+        let mut rng = Rng(0xbe4c_4a12);
+        let moves = ["R", "L", "U", "D"];
+        let wire1 = random_wire(&mut rng, 100_000, 1_000, &moves);
+        let wire2 = random_wire(&mut rng, 100_000, 1_000, &moves);
+        let (lines1, lines2) = (
+            parse_directions(&wire1).unwrap(),
+            parse_directions(&wire2).unwrap(),
+        );
+
+        let start = Instant::now();
+        let found = crossings(&lines1, &lines2);
+        let sweep = start.elapsed();
+        let start = Instant::now();
+        let distance = crate::closest_intersection_distance(&lines1, &lines2);
+        let steps = crate::closest_intersection_steps(&lines1, &lines2);
+        let queries = start.elapsed();
+        println!(
+            "{} crossings in {:?}; distance {:?} and steps {:?} in {:?}",
+            found.len(),
+            sweep,
+            distance,
+            steps,
+            queries
+        );
+
+        // The pairwise comparison would take 10^10 intersection tests, so it
+        // is only timed on a tenth of each wire.
+        let start = Instant::now();
+        let pairwise = brute_force(&lines1[..10_000], &lines2[..10_000]);
+        println!(
+            "pairwise on 10k segments each: {} crossings in {:?}",
+            pairwise.len(),
+            start.elapsed()
+        );
+        assert_eq!(pairwise, crossings(&lines1[..10_000], &lines2[..10_000]));
+    }
+}
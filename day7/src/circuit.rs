@@ -0,0 +1,467 @@
+//! Chains of amplifiers that all run the same Intcode program.
+//!
+//! The program is parsed once, and every amplifier starts as a clone of that
+//! image. Searches spread the phase permutations over several threads, so that
+//! circuits with eight or ten amplifiers stay practical, and reuse amplifiers
+//! that permutations with a common prefix of phase settings share. Which
+//! amplifiers are reused does not depend on how the work is split between
+//! threads. `protocol` describes how the amplifiers talk to each other.
+
+use crate::protocol::{self, Amplifier};
+use intcode_computer::IntcodeComputer;
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::thread;
+
+/// How the amplifiers are wired together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    /// The signal passes through every amplifier once.
+    Series,
+    /// The last amplifier feeds back into the first until it halts.
+    Feedback,
+}
+
+pub struct AmplifierCircuit {
+    image: IntcodeComputer,
+    amplifiers: usize,
+    phase_settings: Vec<i32>,
+    topology: Topology,
+    threads: usize,
+}
+
+/// The signal of a run along with the indices of its phase settings, which
+/// break ties in favour of the permutation that comes first.
+type Candidate = (i32, Vec<usize>);
+
+/// Orders better candidates first.
+fn rank(a: &Candidate, b: &Candidate) -> Ordering {
+    b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1))
+}
+
+/// What one thread found.
+struct Partial {
+    best: Option<(Candidate, Run)>,
+    ranking: Vec<Candidate>,
+    vm_runs: usize,
+    vm_runs_saved: usize,
+}
+
+/// An amplifier after the first pass of the signal, as part of a shared prefix.
+#[derive(Clone)]
+struct Stage {
+    phase: i32,
+    amplifier: Amplifier,
+}
+
+impl AmplifierCircuit {
+    /// A circuit of `amplifiers` amplifiers, each of which gets a different
+    /// phase setting from `phase_settings`.
+    pub fn new(
+        raw_code: &str,
+        amplifiers: usize,
+        phase_settings: Vec<i32>,
+        topology: Topology,
+    ) -> Self {
+        Self {
+            image: IntcodeComputer::new(raw_code, Vec::new()),
+            amplifiers,
+            phase_settings,
+            topology,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// Searches with `threads` threads instead of one per available core.
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            ..self
+        }
+    }
+
+    /// Runs the circuit with the amplifiers getting `phases`, in order.
+    pub fn run(&self, phases: &[i32]) -> Result<Run, String> {
+        if phases.is_empty() || phases.len() != self.amplifiers {
+            return Err(format!(
+                "Expected {} phase settings, got {}.",
+                self.amplifiers,
+                phases.len()
+            ));
+        }
+        let mut amplifiers: Vec<Amplifier> = phases
+            .iter()
+            .map(|&phase| Amplifier::boot(&self.image, phase))
+            .collect();
+        protocol::first_pass(&mut amplifiers, 0)?;
+        self.finish(phases, amplifiers)
+    }
+
+    /// Completes a run once the signal has been through every amplifier once.
+    fn finish(&self, phases: &[i32], mut amplifiers: Vec<Amplifier>) -> Result<Run, String> {
+        let feedback_loops = match self.topology {
+            Topology::Series => 0,
+            Topology::Feedback => {
+                let carry = amplifiers.last().unwrap().signals[0].clone();
+                protocol::feedback(&mut amplifiers, carry)?
+            }
+        };
+        Ok(Run {
+            phases: phases.to_vec(),
+            signal: protocol::thruster_signal(&amplifiers)?,
+            signals: amplifiers
+                .into_iter()
+                .map(|amplifier| amplifier.signals)
+                .collect(),
+            feedback_loops,
+        })
+    }
+
+    /// Runs every assignment of distinct phase settings and keeps the `top_n`
+    /// best ones. Ties go to the assignment that comes first in permutation order.
+    pub fn search(&self, top_n: usize) -> Result<Search, String> {
+        if self.amplifiers == 0 || self.amplifiers > self.phase_settings.len() {
+            return Err(format!(
+                "Cannot assign {} distinct phase settings out of {:?}.",
+                self.amplifiers, self.phase_settings
+            ));
+        }
+        // Every job starts from one of these, so each is only run once.
+        let first_stages = self
+            .phase_settings
+            .iter()
+            .map(|&phase| {
+                let mut amplifier = Amplifier::boot(&self.image, phase);
+                amplifier.receive(0, &[0])?;
+                Ok(Stage { phase, amplifier })
+            })
+            .collect::<Result<Vec<Stage>, String>>()?;
+        // Each job fixes the phase settings of the first two amplifiers.
+        let jobs: Vec<Vec<usize>> = (0..self.phase_settings.len())
+            .permutations(self.amplifiers.min(2))
+            .collect();
+        let next_job = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let partials: Vec<Result<Partial, String>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(jobs.len()))
+                .map(|_| {
+                    scope
+                        .spawn(|| self.search_jobs(&jobs, &first_stages, &next_job, &failed, top_n))
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        let mut best: Option<(Candidate, Run)> = None;
+        let mut ranking = Vec::new();
+        // The first use of each first stage counts as a run, not as a saving.
+        let (mut vm_runs, mut vm_runs_saved) = (first_stages.len(), 0);
+        for partial in partials {
+            let partial = partial?;
+            vm_runs += partial.vm_runs;
+            vm_runs_saved += partial.vm_runs_saved;
+            if let Some((candidate, run)) = partial.best {
+                if best
+                    .as_ref()
+                    .is_none_or(|(best, _)| rank(&candidate, best).is_lt())
+                {
+                    best = Some((candidate, run));
+                }
+            }
+            ranking.extend(partial.ranking);
+        }
+        vm_runs_saved -= first_stages.len();
+        ranking.sort_by(rank);
+        ranking.truncate(top_n);
+        Ok(Search {
+            best: best.unwrap().1,
+            ranking: ranking
+                .into_iter()
+                .map(|(signal, indices)| (signal, self.phases(&indices)))
+                .collect(),
+            vm_runs,
+            vm_runs_saved,
+        })
+    }
+
+    fn phases(&self, indices: &[usize]) -> Vec<i32> {
+        indices.iter().map(|&i| self.phase_settings[i]).collect()
+    }
+
+    /// Works through jobs until there are none left or another thread failed.
+    fn search_jobs(
+        &self,
+        jobs: &[Vec<usize>],
+        first_stages: &[Stage],
+        next_job: &AtomicUsize,
+        failed: &AtomicBool,
+        top_n: usize,
+    ) -> Result<Partial, String> {
+        let mut partial = Partial {
+            best: None,
+            ranking: Vec::new(),
+            vm_runs: 0,
+            vm_runs_saved: 0,
+        };
+        while !failed.load(atomic::Ordering::Relaxed) {
+            let prefix = match jobs.get(next_job.fetch_add(1, atomic::Ordering::Relaxed)) {
+                Some(prefix) => prefix,
+                None => break,
+            };
+            let first_stage = first_stages[prefix[0]].clone();
+            if let Err(error) = self.search_job(prefix, first_stage, &mut partial, top_n) {
+                failed.store(true, atomic::Ordering::Relaxed);
+                return Err(error);
+            }
+        }
+        Ok(partial)
+    }
+
+    /// Runs every permutation that starts with the phase settings at `prefix`,
+    /// starting from `first_stage`, the first amplifier after its first pass.
+    ///
+    /// Permutations are visited in order, so all permutations that share a
+    /// prefix come one after the other, and the amplifiers for that prefix are
+    /// only booted once. The signal entering the prefix is always the circuit's
+    /// input signal, so the prefix alone determines the amplifiers' state. Only
+    /// amplifiers within the job are reused, so the number of runs saved is the
+    /// same however jobs are spread over threads.
+    fn search_job(
+        &self,
+        prefix: &[usize],
+        first_stage: Stage,
+        partial: &mut Partial,
+        top_n: usize,
+    ) -> Result<(), String> {
+        let mut path = vec![first_stage];
+        let rest: Vec<usize> = (0..self.phase_settings.len())
+            .filter(|i| !prefix.contains(i))
+            .collect();
+        for suffix in rest
+            .into_iter()
+            .permutations(self.amplifiers - prefix.len())
+        {
+            let indices: Vec<usize> = prefix.iter().copied().chain(suffix).collect();
+            let phases = self.phases(&indices);
+            let shared = path
+                .iter()
+                .zip(&phases)
+                .take_while(|(stage, &phase)| stage.phase == phase)
+                .count();
+            path.truncate(shared);
+            partial.vm_runs_saved += shared;
+            for (i, &phase) in phases.iter().enumerate().skip(shared) {
+                let signals = match path.last() {
+                    Some(previous) => previous.amplifier.signals[0].clone(),
+                    None => vec![0],
+                };
+                let mut amplifier = Amplifier::boot(&self.image, phase);
+                amplifier.receive(i, &signals)?;
+                partial.vm_runs += 1;
+                path.push(Stage { phase, amplifier });
+            }
+            let amplifiers = path.iter().map(|stage| stage.amplifier.clone()).collect();
+            let run = self.finish(&phases, amplifiers)?;
+
+            let candidate = (run.signal, indices);
+            let position = partial
+                .ranking
+                .partition_point(|other| rank(other, &candidate).is_lt());
+            if position < top_n {
+                partial.ranking.insert(position, candidate.clone());
+                partial.ranking.truncate(top_n);
+            }
+            if partial
+                .best
+                .as_ref()
+                .is_none_or(|(best, _)| rank(&candidate, best).is_lt())
+            {
+                partial.best = Some((candidate, run));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of running a circuit with one assignment of phase settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    pub phases: Vec<i32>,
+    /// The signal sent to the thrusters.
+    pub signal: i32,
+    /// The signals each amplifier emitted on each pass: `signals[i][k]` holds
+    /// what amplifier `i` emitted on pass `k`. Amplifiers that halt early take
+    /// part in fewer passes.
+    pub signals: Vec<Vec<Vec<i32>>>,
+    /// How many times the last amplifier's signals went back into the first.
+    pub feedback_loops: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    /// The run that produced the highest signal.
+    pub best: Run,
+    /// The best signals with their phase settings, highest first.
+    pub ranking: Vec<(i32, Vec<i32>)>,
+    /// How many amplifiers were booted and run on their first signals.
+    pub vm_runs: usize,
+    /// How many amplifier boots were skipped by reusing a shared prefix. Like
+    /// `vm_runs`, it does not depend on the number of threads.
+    pub vm_runs_saved: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES_EXAMPLE: &str = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+    const FEEDBACK_EXAMPLE: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+
+    #[test]
+    fn search_returns_winning_phases() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 5, (0..5).collect(), Topology::Series);
+        let best = circuit.search(1).unwrap().best;
+        assert_eq!((best.signal, best.phases), (43210, vec![4, 3, 2, 1, 0]));
+
+        let circuit =
+            AmplifierCircuit::new(FEEDBACK_EXAMPLE, 5, (5..10).collect(), Topology::Feedback);
+        let best = circuit.search(1).unwrap().best;
+        assert_eq!(
+            (best.signal, best.phases),
+            (139_629_729, vec![9, 8, 7, 6, 5])
+        );
+    }
+
+    #[test]
+    fn any_number_of_amplifiers_and_phases() {
+        // Each amplifier computes 10 * signal + phase.
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 3, vec![1, 7, 2, 9], Topology::Series);
+        assert_eq!(circuit.run(&[1, 2, 3]).unwrap().signal, 123);
+        assert_eq!(circuit.search(1).unwrap().best.phases, vec![9, 7, 2]);
+
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 1, vec![3], Topology::Series);
+        assert_eq!(circuit.search(1).unwrap().best.signal, 3);
+    }
+
+    #[test]
+    fn run_records_every_pass() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 3, vec![1, 2, 3], Topology::Series);
+        let run = circuit.run(&[1, 2, 3]).unwrap();
+        assert_eq!(
+            run.signals,
+            vec![vec![vec![1]], vec![vec![12]], vec![vec![123]]]
+        );
+        assert_eq!(run.feedback_loops, 0);
+
+        let circuit =
+            AmplifierCircuit::new(FEEDBACK_EXAMPLE, 5, (5..10).collect(), Topology::Feedback);
+        let run = circuit.run(&[9, 8, 7, 6, 5]).unwrap();
+        assert_eq!(run.feedback_loops, 4);
+        assert!(run.signals.iter().all(|passes| passes.len() == 5));
+        let first_pass: Vec<&[i32]> = run.signals.iter().map(|passes| &passes[0][..]).collect();
+        assert_eq!(first_pass, vec![[5], [14], [31], [64], [129]]);
+        assert_eq!(run.signals[4][4], vec![run.signal]);
+    }
+
+    #[test]
+    fn ranking_keeps_top_n() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 2, vec![1, 2, 3], Topology::Series);
+        let search = circuit.search(3).unwrap();
+        assert_eq!(
+            search.ranking,
+            vec![(32, vec![3, 2]), (31, vec![3, 1]), (23, vec![2, 3])]
+        );
+        assert!(circuit.search(0).unwrap().ranking.is_empty());
+        assert_eq!(circuit.search(10).unwrap().ranking.len(), 6);
+    }
+
+    #[test]
+    fn ties_go_to_the_first_permutation() {
+        // Every amplifier outputs 1, whatever its phase setting.
+        for &threads in [1, 3, 8].iter() {
+            let circuit =
+                AmplifierCircuit::new("3,0,3,0,104,1,99", 3, vec![5, 3, 4, 1], Topology::Series)
+                    .with_threads(threads);
+            let search = circuit.search(3).unwrap();
+            assert_eq!(search.best.phases, vec![5, 3, 4]);
+            assert_eq!(
+                search.ranking,
+                vec![(1, vec![5, 3, 4]), (1, vec![5, 3, 1]), (1, vec![5, 4, 3])]
+            );
+        }
+    }
+
+    #[test]
+    fn large_phase_sets_match_across_thread_counts() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 7, (0..7).collect(), Topology::Series)
+            .with_threads(4);
+        let search = circuit.search(5).unwrap();
+        assert_eq!(search.best.signal, 6_543_210);
+        assert_eq!(search.best.phases, vec![6, 5, 4, 3, 2, 1, 0]);
+        let serial = AmplifierCircuit::new(SERIES_EXAMPLE, 7, (0..7).collect(), Topology::Series)
+            .with_threads(1)
+            .search(5)
+            .unwrap();
+        assert_eq!(search.best, serial.best);
+        assert_eq!(search.ranking, serial.ranking);
+    }
+
+    #[test]
+    fn shared_prefixes_are_only_run_once() {
+        for &(program, ref phases, topology) in [
+            (SERIES_EXAMPLE, 0..5, Topology::Series),
+            (FEEDBACK_EXAMPLE, 5..10, Topology::Feedback),
+        ]
+        .iter()
+        {
+            let search = AmplifierCircuit::new(program, 5, phases.clone().collect(), topology)
+                .with_threads(1)
+                .search(120)
+                .unwrap();
+            // 5 + 5 * 4 + 5 * 4 * 3 + ... instead of 5 * 120.
+            assert_eq!(search.vm_runs, 325);
+            assert_eq!(search.vm_runs + search.vm_runs_saved, 600);
+            for (signal, phases) in &search.ranking {
+                let circuit = AmplifierCircuit::new(program, 5, phases.clone(), topology);
+                assert_eq!(circuit.run(phases).unwrap().signal, *signal);
+            }
+        }
+    }
+
+    #[test]
+    fn vm_runs_do_not_depend_on_threads() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 5, (0..7).collect(), Topology::Series);
+        let serial = circuit.with_threads(1).search(1).unwrap();
+        for &threads in [2, 3, 8, 64].iter() {
+            let circuit =
+                AmplifierCircuit::new(SERIES_EXAMPLE, 5, (0..7).collect(), Topology::Series)
+                    .with_threads(threads);
+            let search = circuit.search(1).unwrap();
+            assert_eq!(
+                (search.vm_runs, search.vm_runs_saved),
+                (serial.vm_runs, serial.vm_runs_saved)
+            );
+        }
+    }
+
+    #[test]
+    fn errors_stop_the_search() {
+        let circuit = AmplifierCircuit::new("3,0,3,0,99", 3, (0..6).collect(), Topology::Series);
+        assert_eq!(
+            circuit.search(1).err().unwrap(),
+            "Amplifier #2 never sent a signal to the thrusters."
+        );
+    }
+
+    #[test]
+    fn too_few_phase_settings_is_an_error() {
+        let circuit = AmplifierCircuit::new(SERIES_EXAMPLE, 3, vec![0, 1], Topology::Series);
+        assert!(circuit.search(1).is_err());
+        assert!(circuit.run(&[0, 1]).is_err());
+    }
+}
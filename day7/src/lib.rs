@@ -1,118 +1,54 @@
-use intcode_computer::*;
-use itertools::Itertools;
-use std::collections::HashSet;
-use std::i32;
+pub mod circuit;
+mod protocol;
 
-mod pt1 {
-    use super::*;
-
-    fn helper(
-        raw_code: &str,
-        input: i32,
-        used_phase_settings: HashSet<i32>,
-        recursion_counter: u32,
-        biggest: &mut i32,
-    ) {
-        if recursion_counter < 5 {
-            for x in 0..5 {
-                if !used_phase_settings.contains(&x) {
-                    let mut computer = IntcodeComputer::new(raw_code, vec![x, input]);
-                    computer.run().unwrap();
-                    let output = computer.output[0];
-                    let mut used_phase_settings_clone = used_phase_settings.clone();
-                    used_phase_settings_clone.insert(x);
-                    helper(
-                        raw_code,
-                        output,
-                        used_phase_settings_clone,
-                        recursion_counter + 1,
-                        biggest,
-                    );
-                }
-            }
-        } else if input > *biggest {
-            *biggest = input;
-        }
-    }
+pub use circuit::{AmplifierCircuit, Run, Search, Topology};
 
-    pub fn pt1(raw_code: &str) -> i32 {
-        let mut biggest = i32::MIN;
-        helper(raw_code, 0, HashSet::new(), 0, &mut biggest);
-
-        biggest
-    }
+pub fn pt1(raw_code: &str) -> Result<i32, String> {
+    let search =
+        AmplifierCircuit::new(raw_code, 5, (0..5).collect(), Topology::Series).search(1)?;
+    Ok(search.best.signal)
 }
 
-pub use pt1::pt1;
-
-mod pt2 {
-    use super::*;
-    pub fn pt2(raw_code: &str) -> Result<i32, String> {
-        let mut biggest = i32::MIN;
-
-        for phase_settings in (5..10).permutations(5) {
-            let mut computers: Vec<IntcodeComputer> = phase_settings
-                .iter()
-                .map(|&phase_setting| IntcodeComputer::new(raw_code, vec![phase_setting]))
-                .collect();
-
-            for computer in &mut computers {
-                computer.run()?;
-            }
-
-            let mut i = 0;
-            let mut next_input = 0;
-
-            while computers.last().unwrap().state != State::Terminated {
-                let computer = &mut computers[i % 5];
-                let old_output_len = computer.output.len();
-                computer.feed_input(next_input)?;
-                let num_outputs_produced = computer.output.len() - old_output_len;
-                if num_outputs_produced != 1 {
-                    panic!("Computer #{} produced more than one output", i % 5)
-                }
-                i += 1;
-                next_input = *computer.output.last().unwrap();
-            }
-
-            let &signal = computers.last().unwrap().output.last().unwrap();
-
-            if signal > biggest {
-                biggest = signal;
-            }
-        }
-
-        Ok(biggest)
-    }
+pub fn pt2(raw_code: &str) -> Result<i32, String> {
+    let search =
+        AmplifierCircuit::new(raw_code, 5, (5..10).collect(), Topology::Feedback).search(1)?;
+    Ok(search.best.signal)
 }
 
-pub use pt2::pt2;
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use intcode_computer::read_file;
     #[test]
     fn pt1_test1() {
         let raw_code = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
-        assert_eq!(pt1(raw_code), 43210);
+        assert_eq!(pt1(raw_code).unwrap(), 43210);
     }
 
     #[test]
     fn pt1_test2() {
         let raw_code = "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0";
-        assert_eq!(pt1(raw_code), 54321);
+        assert_eq!(pt1(raw_code).unwrap(), 54321);
     }
 
     #[test]
     fn pt1_test3() {
         let raw_code = "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0";
-        assert_eq!(pt1(raw_code), 65210);
+        assert_eq!(pt1(raw_code).unwrap(), 65210);
     }
 
     #[test]
     fn pt1_test() {
         let raw_code = read_file("input");
-        assert_eq!(pt1(&raw_code), 21000);
+        assert_eq!(pt1(&raw_code).unwrap(), 21000);
+    }
+
+    #[test]
+    fn pt1_reports_broken_programs() {
+        assert_eq!(
+            pt1("99").err().unwrap(),
+            "Amplifier #0 halted with 2 unread signal(s)."
+        );
     }
 
     #[test]
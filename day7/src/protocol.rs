@@ -0,0 +1,215 @@
+//! How amplifiers pass signals to each other.
+//!
+//! Amplifiers take turns in order. Every signal an amplifier emits is forwarded,
+//! in order, to the next amplifier, and in a feedback circuit the last
+//! amplifier's signals go back to the first one. Amplifiers may emit any number
+//! of signals per input. A series circuit is done after one pass; a feedback
+//! circuit is done once every amplifier has halted, in whatever order they halt.
+//! The last signal of the last amplifier goes to the thrusters.
+//!
+//! Everything else is reported as an error: an amplifier that halts with unread
+//! signals, a signal sent to an amplifier that has halted, amplifiers waiting
+//! for signals that will never arrive, or no signal for the thrusters at all.
+
+use intcode_computer::{IntcodeComputer, State};
+
+#[derive(Clone)]
+pub(crate) struct Amplifier {
+    computer: IntcodeComputer,
+    /// The signals the amplifier emitted on each pass, starting with the first.
+    pub(crate) signals: Vec<Vec<i32>>,
+}
+
+impl Amplifier {
+    /// An amplifier running `image` that has been given `phase`, but not run yet.
+    pub(crate) fn boot(image: &IntcodeComputer, phase: i32) -> Self {
+        let mut computer = image.clone();
+        computer.queue_input(phase);
+        Self {
+            computer,
+            signals: Vec::new(),
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.computer.state == State::Terminated
+    }
+
+    /// Hands `signals` to amplifier `i` and runs it until it halts or needs
+    /// more input, as one pass. Returns the signals it emitted in the meantime.
+    pub(crate) fn receive(&mut self, i: usize, signals: &[i32]) -> Result<Vec<i32>, String> {
+        if self.halted() {
+            if signals.is_empty() {
+                return Ok(Vec::new());
+            }
+            return Err(format!(
+                "Amplifier #{} was sent {} signal(s) after it halted.",
+                i,
+                signals.len()
+            ));
+        }
+        for &signal in signals {
+            self.computer.queue_input(signal);
+        }
+        let old_output_len = self.computer.output.len();
+        self.computer
+            .run()
+            .map_err(|error| format!("Amplifier #{} failed: {}", i, error))?;
+        let unread = self.computer.pending_input().count();
+        if self.halted() && unread > 0 {
+            return Err(format!(
+                "Amplifier #{} halted with {} unread signal(s).",
+                i, unread
+            ));
+        }
+        let emitted = self.computer.output[old_output_len..].to_vec();
+        self.signals.push(emitted.clone());
+        Ok(emitted)
+    }
+}
+
+/// Sends `input` through amplifiers that have not run yet, in order, and
+/// returns what the last one emitted.
+pub(crate) fn first_pass(amplifiers: &mut [Amplifier], input: i32) -> Result<Vec<i32>, String> {
+    let mut signals = vec![input];
+    for (i, amplifier) in amplifiers.iter_mut().enumerate() {
+        signals = amplifier.receive(i, &signals)?;
+    }
+    Ok(signals)
+}
+
+/// Keeps passing the last amplifier's signals back to the first one until
+/// every amplifier has halted. `carry` holds the signals on their way back.
+/// Returns how many passes the signals made back through the circuit.
+pub(crate) fn feedback(amplifiers: &mut [Amplifier], mut carry: Vec<i32>) -> Result<usize, String> {
+    let mut fed_back = 0;
+    while !amplifiers.iter().all(Amplifier::halted) {
+        if carry.is_empty() {
+            let waiting: Vec<usize> = (0..amplifiers.len())
+                .filter(|&i| !amplifiers[i].halted())
+                .collect();
+            return Err(format!(
+                "Deadlock: amplifier(s) {:?} wait for signals, but none are on their way.",
+                waiting
+            ));
+        }
+        fed_back += 1;
+        for (i, amplifier) in amplifiers.iter_mut().enumerate() {
+            carry = amplifier.receive(i, &carry)?;
+        }
+    }
+    Ok(fed_back)
+}
+
+/// The last signal the last amplifier emitted.
+pub(crate) fn thruster_signal(amplifiers: &[Amplifier]) -> Result<i32, String> {
+    let last = amplifiers.len() - 1;
+    amplifiers[last]
+        .signals
+        .iter()
+        .rev()
+        .find_map(|signals| signals.last())
+        .copied()
+        .ok_or_else(|| format!("Amplifier #{} never sent a signal to the thrusters.", last))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AmplifierCircuit, Topology};
+    use intcode_computer::lang::compile;
+
+    fn run(source: &str, phases: &[i32], topology: Topology) -> Result<crate::Run, String> {
+        let raw_code = compile(source).unwrap();
+        AmplifierCircuit::new(&raw_code, phases.len(), phases.to_vec(), topology).run(phases)
+    }
+
+    #[test]
+    fn every_signal_is_forwarded_in_order() {
+        // Reads `phase` signals and answers each with two.
+        let source = "
+            phase = input();
+            while phase > 0 {
+                x = input();
+                output(x + 1);
+                output(x + 2);
+                phase = phase - 1;
+            }";
+        let run = run(source, &[1, 2], Topology::Series).unwrap();
+        assert_eq!(run.signals, vec![vec![vec![1, 2]], vec![vec![2, 3, 3, 4]]]);
+        assert_eq!(run.signal, 4);
+    }
+
+    #[test]
+    fn feedback_loops_count_passes_not_signals() {
+        // Answers the first signal with two, then every two with two more.
+        let source = "
+            phase = input();
+            x = input();
+            output(x);
+            output(x + 1);
+            while phase > 0 {
+                a = input();
+                b = input();
+                output(a + b);
+                output(b);
+                phase = phase - 1;
+            }";
+        let run = run(source, &[2], Topology::Feedback).unwrap();
+        assert_eq!(run.signals, vec![vec![vec![0, 1], vec![1, 1], vec![2, 1]]]);
+        assert_eq!(run.feedback_loops, 2);
+        assert_eq!(run.signal, 1);
+    }
+
+    #[test]
+    fn halting_with_unread_signals_is_an_error() {
+        let source = "phase = input(); x = input(); output(x); output(x);";
+        assert_eq!(
+            run(source, &[0, 0], Topology::Series).err().unwrap(),
+            "Amplifier #1 halted with 1 unread signal(s)."
+        );
+    }
+
+    #[test]
+    fn signals_to_halted_amplifiers_are_an_error() {
+        let source = "
+            phase = input();
+            while phase > 0 {
+                output(input() + 1);
+                phase = phase - 1;
+            }";
+        assert_eq!(
+            run(source, &[2, 1], Topology::Feedback).err().unwrap(),
+            "Amplifier #1 was sent 1 signal(s) after it halted."
+        );
+    }
+
+    #[test]
+    fn deadlock_is_detected() {
+        let source = "phase = input(); x = input(); y = input(); output(x + y);";
+        assert_eq!(
+            run(source, &[0, 1], Topology::Feedback).err().unwrap(),
+            "Deadlock: amplifier(s) [0, 1] wait for signals, but none are on their way."
+        );
+    }
+
+    #[test]
+    fn last_amplifier_may_halt_first() {
+        let source = "
+            phase = input();
+            output(input() + 1);
+            if phase == 1 { x = input(); }";
+        let run = run(source, &[1, 0], Topology::Feedback).unwrap();
+        assert_eq!(run.signal, 2);
+        assert_eq!(run.feedback_loops, 1);
+    }
+
+    #[test]
+    fn missing_thruster_signal_is_an_error() {
+        assert_eq!(
+            run("phase = input(); x = input();", &[0], Topology::Series)
+                .err()
+                .unwrap(),
+            "Amplifier #0 never sent a signal to the thrusters."
+        );
+    }
+}
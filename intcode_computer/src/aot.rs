@@ -0,0 +1,131 @@
+//! Ahead-of-time compilation of Intcode programs into Rust.
+//!
+//! `compile` turns a program into the source of a `Compiled` function, and
+//! `IntcodeComputer::run_compiled` executes it, falling back to the interpreter
+//! whenever the compiled code cannot continue on its own. Compiled functions
+//! always implement the default instruction set.
+
+use crate::{IntcodeComputer, State};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+mod compiler;
+
+pub use compiler::compile;
+
+/// Why a compiled function returned, and the position to resume from.
+pub enum Exit {
+    Halted(usize),
+    WaitingForInput(usize),
+    /// The interpreter should take over at this position.
+    Fallback(usize),
+}
+
+/// The input and output queues of the computer running a compiled function.
+pub struct Io<'a> {
+    input: &'a mut VecDeque<i32>,
+    output: &'a mut Vec<i32>,
+}
+
+impl<'a> Io<'a> {
+    /// Queues for a compiled function. Input is consumed from the back of
+    /// `input`, like a computer's pending input.
+    pub fn new(input: &'a mut VecDeque<i32>, output: &'a mut Vec<i32>) -> Self {
+        Self { input, output }
+    }
+
+    pub fn consume_input(&mut self) -> Option<i32> {
+        self.input.pop_back()
+    }
+
+    pub fn produce_output(&mut self, output: i32) {
+        self.output.push(output);
+    }
+}
+
+pub type Compiled = fn(&mut [i32], usize, &mut Io) -> Exit;
+
+/// Returns the value at `address`, if it is a valid address.
+#[inline]
+pub fn load(memory: &[i32], address: i32) -> Option<i32> {
+    usize::try_from(address)
+        .ok()
+        .and_then(|address| memory.get(address).copied())
+}
+
+/// Returns `address` as an index into memory, if it is a valid address.
+#[inline]
+pub fn address(memory: &[i32], address: i32) -> Option<usize> {
+    usize::try_from(address)
+        .ok()
+        .filter(|&address| address < memory.len())
+}
+
+impl IntcodeComputer {
+    /// Like `run`, but executes `compiled` instead of interpreting the program.
+    /// Computers with a custom instruction set, memory-mapped devices or
+    /// history are always interpreted.
+    pub fn run_compiled(&mut self, compiled: Compiled) -> Result<(), String> {
+        if !self.instruction_set.is_default() || !self.devices.is_empty() || self.history.is_some()
+        {
+            return self.run();
+        }
+        self.state = State::Running;
+        self.blocks.clear();
+        let mut io = Io {
+            input: &mut self.input,
+            output: &mut self.output,
+        };
+        match compiled(&mut self.intcode, self.position, &mut io) {
+            Exit::Halted(position) => {
+                self.position = position;
+                self.state = State::Terminated;
+                Ok(())
+            }
+            Exit::WaitingForInput(position) => {
+                self.position = position;
+                self.state = State::WaitingForInput;
+                Ok(())
+            }
+            Exit::Fallback(position) => {
+                self.position = position;
+                self.run()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a compiled program: halts without doing anything.
+    fn halt(_: &mut [i32], position: usize, _: &mut Io) -> Exit {
+        Exit::Halted(position)
+    }
+
+    #[test]
+    fn custom_instruction_sets_are_interpreted() {
+        // Adds are replaced by multiplications.
+        let mut instruction_set = crate::InstructionSet::default();
+        let multiply = instruction_set.handler(2).unwrap();
+        instruction_set.register(1, 3, move |opcode, computer| multiply(opcode, computer));
+        let mut computer = IntcodeComputer::with_instruction_set(
+            "1,9,10,3,2,3,11,0,99,30,40,50",
+            Vec::new(),
+            alloc::sync::Arc::new(instruction_set),
+        );
+        computer.run_compiled(halt).unwrap();
+        assert_eq!(computer.memory()[0], 30 * 40 * 50);
+    }
+
+    #[test]
+    fn default_computers_run_compiled_code() {
+        let mut computer = IntcodeComputer::new("1,9,10,3,2,3,11,0,99,30,40,50", Vec::new());
+        computer.run_compiled(halt).unwrap();
+        assert_eq!(computer.memory()[0], 1);
+        assert_eq!(computer.state, State::Terminated);
+    }
+}
@@ -0,0 +1,235 @@
+//! Translates an Intcode program into Rust source.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+struct Instruction {
+    opcode: i32,
+    modes: Vec<i32>,
+}
+
+fn num_operands(opcode: i32) -> Option<usize> {
+    match opcode {
+        1 | 2 | 7 | 8 => Some(3),
+        3 | 4 => Some(1),
+        5 | 6 => Some(2),
+        99 => Some(0),
+        _ => None,
+    }
+}
+
+/// Decodes the instruction at `position`, or returns `None` if the compiled
+/// code should leave it to the interpreter.
+fn decode(program: &[i32], position: usize) -> Option<Instruction> {
+    let num = program[position];
+    if num < 0 {
+        return None;
+    }
+    let opcode = num % 100;
+    let num_operands = num_operands(opcode)?;
+    if position + num_operands >= program.len() {
+        return None;
+    }
+    let modes: Vec<i32> = (0..num_operands)
+        .map(|i| num / 10_i32.pow(i as u32 + 2) % 10)
+        .collect();
+    if opcode != 3 && modes.iter().any(|&mode| mode > 1) {
+        return None;
+    }
+    Some(Instruction { opcode, modes })
+}
+
+/// Decodes the instructions found by a linear sweep of the program, plus those
+/// reachable from them through fall-through and immediate jump targets.
+/// Indirect jumps land on the sweep's instructions or fall back.
+fn decode_program(program: &[i32]) -> BTreeMap<usize, Instruction> {
+    let mut pending = Vec::new();
+    let mut position = 0;
+    while position < program.len() {
+        pending.push(position);
+        position += decode(program, position).map_or(1, |instruction| 1 + instruction.modes.len());
+    }
+
+    let mut instructions = BTreeMap::new();
+    while let Some(position) = pending.pop() {
+        if position >= program.len() || instructions.contains_key(&position) {
+            continue;
+        }
+        let instruction = match decode(program, position) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let next = position + 1 + instruction.modes.len();
+        match instruction.opcode {
+            99 => {}
+            5 | 6 => {
+                let target = program[position + 2];
+                if instruction.modes[1] == 1 && target >= 0 {
+                    pending.push(target as usize);
+                }
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+        instructions.insert(position, instruction);
+    }
+    instructions
+}
+
+fn param(position: usize, instruction: &Instruction, n: usize) -> String {
+    if instruction.modes[n] == 1 {
+        format!("m[{}]", position + 1 + n)
+    } else {
+        format!(
+            "or_fallback!(load(m, m[{}]), {})",
+            position + 1 + n,
+            position
+        )
+    }
+}
+
+fn write_instruction(code: &mut String, position: usize, instruction: &Instruction) {
+    let next = position + 1 + instruction.modes.len();
+    let mut lines = Vec::new();
+    let target = |n: usize| {
+        format!(
+            "let t = or_fallback!(address(m, m[{}]), {});",
+            position + 1 + n,
+            position
+        )
+    };
+    let store = |value: &str| {
+        vec![
+            format!("m[t] = {};", value),
+            format!("if is_code(t) {{ return Exit::Fallback({}); }}", next),
+        ]
+    };
+
+    match instruction.opcode {
+        1 | 2 | 7 | 8 => {
+            lines.push(format!("let a = {};", param(position, instruction, 0)));
+            lines.push(format!("let b = {};", param(position, instruction, 1)));
+            lines.push(target(2));
+            let value = match instruction.opcode {
+                1 => format!("or_fallback!(a.checked_add(b), {})", position),
+                2 => format!("or_fallback!(a.checked_mul(b), {})", position),
+                7 => String::from("(a < b) as i32"),
+                _ => String::from("(a == b) as i32"),
+            };
+            lines.extend(store(&value));
+        }
+        3 => {
+            lines.push(target(0));
+            lines.push(format!(
+                "let v = match io.consume_input() {{ Some(v) => v, None => return Exit::WaitingForInput({}) }};",
+                position
+            ));
+            lines.extend(store("v"));
+        }
+        4 => {
+            lines.push(format!(
+                "io.produce_output({});",
+                param(position, instruction, 0)
+            ));
+        }
+        5 | 6 => {
+            let condition = if instruction.opcode == 5 { "!=" } else { "==" };
+            lines.push(format!("let a = {};", param(position, instruction, 0)));
+            lines.push(format!("let b = {};", param(position, instruction, 1)));
+            lines.push(format!("if a {} 0 {{", condition));
+            lines.push(format!(
+                "    if b < 0 {{ return Exit::Fallback({}); }}",
+                position
+            ));
+            lines.push(String::from("    pc = b as usize;"));
+            lines.push(String::from("    continue;"));
+            lines.push(String::from("}"));
+        }
+        _ => lines.push(format!("return Exit::Halted({});", position)),
+    }
+    if instruction.opcode != 99 {
+        lines.push(format!("pc = {};", next));
+    }
+
+    writeln!(code, "            {} => {{", position).unwrap();
+    for line in lines {
+        writeln!(code, "                {}", line).unwrap();
+    }
+    writeln!(code, "            }}").unwrap();
+}
+
+/// Returns the source of a function named `function_name` that implements
+/// `aot::Compiled` for `program`.
+///
+/// Opcodes and parameter modes are decoded statically, while operands are read
+/// from memory at run time, so patching operands (like day 2's noun and verb)
+/// keeps the compiled code valid. Anything the compiled code cannot handle
+/// exactly like the interpreter, including writes to a decoded opcode, hands
+/// control back to the interpreter.
+pub fn compile(program: &[i32], function_name: &str) -> String {
+    let instructions = decode_program(program);
+    let mut code = String::new();
+
+    writeln!(
+        code,
+        "#[allow(unused_macros, unused_variables, clippy::all)]"
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "pub fn {}(m: &mut [i32], pc: usize, io: &mut intcode_computer::aot::Io) -> intcode_computer::aot::Exit {{",
+        function_name
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "    use intcode_computer::aot::{{address, load, Exit}};"
+    )
+    .unwrap();
+    writeln!(code, "    macro_rules! or_fallback {{").unwrap();
+    writeln!(code, "        ($value:expr, $pc:expr) => {{").unwrap();
+    writeln!(code, "            match $value {{").unwrap();
+    writeln!(code, "                Some(value) => value,").unwrap();
+    writeln!(code, "                None => return Exit::Fallback($pc),").unwrap();
+    writeln!(code, "            }}").unwrap();
+    writeln!(code, "        }};").unwrap();
+    writeln!(code, "    }}").unwrap();
+    let opcode_cells: Vec<String> = instructions.keys().map(|p| p.to_string()).collect();
+    writeln!(code, "    fn is_code(address: usize) -> bool {{").unwrap();
+    if opcode_cells.is_empty() {
+        writeln!(code, "        let _ = address;").unwrap();
+        writeln!(code, "        false").unwrap();
+    } else {
+        writeln!(
+            code,
+            "        matches!(address, {})",
+            opcode_cells.join(" | ")
+        )
+        .unwrap();
+    }
+    writeln!(code, "    }}").unwrap();
+    let checks: Vec<String> = instructions
+        .keys()
+        .map(|&p| format!("m[{}] != {}", p, program[p]))
+        .collect();
+    writeln!(code, "    if m.len() != {}", program.len()).unwrap();
+    for check in checks {
+        writeln!(code, "        || {}", check).unwrap();
+    }
+    writeln!(code, "    {{").unwrap();
+    writeln!(code, "        return Exit::Fallback(pc);").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "    let mut pc = pc;").unwrap();
+    writeln!(code, "    loop {{").unwrap();
+    writeln!(code, "        match pc {{").unwrap();
+    for (&position, instruction) in &instructions {
+        write_instruction(&mut code, position, instruction);
+    }
+    writeln!(code, "            _ => return Exit::Fallback(pc),").unwrap();
+    writeln!(code, "        }}").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+    code
+}
@@ -0,0 +1,16 @@
+use intcode_computer::{aot, parse_intcode, read_file};
+use std::env;
+
+/// Prints the Rust source of a compiled Intcode program.
+///
+/// Usage: intcode_aot <path to program> [function name]
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .expect("Usage: intcode_aot <path to program> [function name]");
+    let function_name = args.next().unwrap_or_else(|| String::from("run"));
+
+    let program = parse_intcode(&read_file(&path));
+    print!("{}", aot::compile(&program, &function_name));
+}
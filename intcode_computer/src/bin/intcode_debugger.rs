@@ -0,0 +1,43 @@
+use intcode_computer::debugger::{Debugger, Outcome, HELP};
+use intcode_computer::{read_file, IntcodeComputer};
+use std::env;
+use std::io::{self, BufRead, Write};
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Debugs an Intcode program interactively.
+///
+/// Usage: intcode_debugger <path to program> [input...]
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode_debugger <path to program> [input...]");
+            std::process::exit(2);
+        }
+    };
+    let input: Result<Vec<i32>, _> = args.map(|arg| arg.parse()).collect();
+    let input = input.unwrap_or_else(|_| {
+        eprintln!("Inputs must be integers.");
+        std::process::exit(2);
+    });
+
+    let mut debugger = Debugger::new(IntcodeComputer::new(&read_file(&path), input));
+    let mut message = String::from(HELP);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}{}\n{}\n(idb) ", CLEAR_SCREEN, debugger.render(), message);
+        io::stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        message = match debugger.execute(&line) {
+            Ok(Outcome::Continue(message)) => message,
+            Ok(Outcome::Quit) => break,
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+}
@@ -0,0 +1,404 @@
+//! An interactive debugger for `IntcodeComputer`, driven by text commands.
+//! `src/bin/intcode_debugger.rs` wraps it in a terminal front end.
+
+use crate::disassemble::{disassemble, instruction_starts};
+use crate::{IntcodeComputer, State};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+const RESET: &str = "\x1b[0m";
+const CURRENT: &str = "\x1b[1;32m";
+const WRITTEN: &str = "\x1b[1;33m";
+const BREAKPOINT: &str = "\x1b[1;31m";
+
+/// How many steps `continue` takes before giving up on reaching a stop.
+const MAX_CONTINUE_STEPS: usize = 10_000_000;
+
+/// History is checkpointed every `CHECKPOINT_INTERVAL` steps, and at most
+/// `MAX_CHECKPOINTS` checkpoints are kept.
+const CHECKPOINT_INTERVAL: usize = 1_000;
+const MAX_CHECKPOINTS: usize = 1_000;
+
+pub const HELP: &str = "\
+step [n]            execute n instructions (default 1)
+continue            run until a breakpoint, a watched write, or the computer stops
+back [n]            undo n instructions (default 1)
+reverse             undo instructions until a breakpoint
+writer <address>    show which instruction last wrote an address
+break <address>     toggle a breakpoint
+watch <address>     toggle a watch on writes to an address
+set <address> <v>   write v to memory, discarding the history
+feed <v>...         queue input values
+help                show this message
+quit                leave the debugger";
+
+pub enum Outcome {
+    /// Keep reading commands. Carries a message for the user.
+    Continue(String),
+    Quit,
+}
+
+pub struct Debugger {
+    pub computer: IntcodeComputer,
+    breakpoints: BTreeSet<usize>,
+    watches: BTreeSet<usize>,
+    /// Cells written by the last command.
+    recently_written: BTreeSet<usize>,
+}
+
+fn parse_number<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    arg.ok_or_else(|| format!("Missing {}.", what))?
+        .parse()
+        .map_err(|_| format!("Invalid {}.", what))
+}
+
+impl Debugger {
+    pub fn new(mut computer: IntcodeComputer) -> Self {
+        computer.record_history(CHECKPOINT_INTERVAL, MAX_CHECKPOINTS);
+        Self {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeSet::new(),
+            recently_written: BTreeSet::new(),
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.computer.state == State::Terminated
+            || (self.computer.state == State::WaitingForInput
+                && self.computer.pending_input().next().is_none())
+    }
+
+    /// Executes one instruction, recording the cell it wrote, if any.
+    fn step(&mut self) -> Result<Option<(usize, i32)>, String> {
+        let written = self.computer.traced_step()?;
+        if let Some((address, _)) = written {
+            self.recently_written.insert(address);
+        }
+        Ok(written)
+    }
+
+    fn run(&mut self, max_steps: usize, stop_at_breakpoints: bool) -> Result<String, String> {
+        self.recently_written.clear();
+        for steps in 0..max_steps {
+            if self.is_stopped() {
+                return Ok(format!("Computer is {:?}.", self.computer.state));
+            }
+            if let Some((address, value)) = self.step()? {
+                if self.watches.contains(&address) {
+                    return Ok(format!(
+                        "Watched address {} written with {}.",
+                        address, value
+                    ));
+                }
+            }
+            if stop_at_breakpoints && self.breakpoints.contains(&self.computer.position()) {
+                return Ok(format!(
+                    "Breakpoint at {} after {} steps.",
+                    self.computer.position(),
+                    steps + 1
+                ));
+            }
+        }
+        if stop_at_breakpoints {
+            Ok(format!("Paused after {} steps.", max_steps))
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    /// Runs a single command line.
+    pub fn execute(&mut self, line: &str) -> Result<Outcome, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Outcome::Continue(String::new())),
+        };
+        let message = match command {
+            "s" | "step" => {
+                let steps = match words.next() {
+                    Some(n) => parse_number(Some(n), "step count")?,
+                    None => 1,
+                };
+                self.run(steps, false)?
+            }
+            "c" | "continue" => self.run(MAX_CONTINUE_STEPS, true)?,
+            "back" => {
+                let steps: usize = match words.next() {
+                    Some(n) => parse_number(Some(n), "step count")?,
+                    None => 1,
+                };
+                self.recently_written.clear();
+                let undone = (0..steps).take_while(|_| self.computer.step_back()).count();
+                format!("Undid {} steps.", undone)
+            }
+            "r" | "reverse" => {
+                self.recently_written.clear();
+                let breakpoints: Vec<usize> = self.breakpoints.iter().copied().collect();
+                let undone = self.computer.reverse_continue(&breakpoints);
+                if breakpoints.contains(&self.computer.position()) && undone > 0 {
+                    format!(
+                        "Breakpoint at {} after undoing {} steps.",
+                        self.computer.position(),
+                        undone
+                    )
+                } else {
+                    format!("Reached the start of the history after {} steps.", undone)
+                }
+            }
+            "writer" => {
+                let address = parse_number(words.next(), "address")?;
+                match self.computer.last_writer(address) {
+                    Some(writer) => format!(
+                        "{} was last written by `{}` at {} in step {}.",
+                        address,
+                        disassemble(self.computer.memory(), writer.position).0,
+                        writer.position,
+                        writer.step
+                    ),
+                    None => format!("{} has not been written in the recorded history.", address),
+                }
+            }
+            "b" | "break" => {
+                let address = parse_number(words.next(), "address")?;
+                if self.breakpoints.remove(&address) {
+                    format!("Removed breakpoint at {}.", address)
+                } else {
+                    self.breakpoints.insert(address);
+                    format!("Added breakpoint at {}.", address)
+                }
+            }
+            "w" | "watch" => {
+                let address = parse_number(words.next(), "address")?;
+                if self.watches.remove(&address) {
+                    format!("Stopped watching {}.", address)
+                } else {
+                    self.watches.insert(address);
+                    format!("Watching {}.", address)
+                }
+            }
+            "set" => {
+                let address = parse_number(words.next(), "address")?;
+                let value = parse_number(words.next(), "value")?;
+                self.computer.set_memory(address, value)?;
+                self.recently_written.clear();
+                self.recently_written.insert(address as usize);
+                format!("Set {} to {}.", address, value)
+            }
+            "f" | "feed" => {
+                let values = words
+                    .map(|word| parse_number(Some(word), "input"))
+                    .collect::<Result<Vec<i32>, String>>()?;
+                for &value in &values {
+                    self.computer.queue_input(value);
+                }
+                format!("Queued {} input(s).", values.len())
+            }
+            "h" | "help" => String::from(HELP),
+            "q" | "quit" => return Ok(Outcome::Quit),
+            _ => return Err(format!("Unknown command `{}`. Try `help`.", command)),
+        };
+        Ok(Outcome::Continue(message))
+    }
+
+    /// Renders the listing around the current position, the memory, the I/O
+    /// queues and the state, using ANSI colours.
+    pub fn render(&self) -> String {
+        let memory = self.computer.memory();
+        let position = self.computer.position();
+        let mut screen = String::new();
+
+        writeln!(
+            screen,
+            "State: {:?}    IP: {}",
+            self.computer.state, position
+        )
+        .unwrap();
+        writeln!(screen).unwrap();
+
+        let starts = instruction_starts(memory, position);
+        let current = starts.iter().position(|&p| p == position).unwrap_or(0);
+        for &start in &starts[current.saturating_sub(4)..(current + 8).min(starts.len())] {
+            let marker = if self.breakpoints.contains(&start) {
+                format!("{}*{}", BREAKPOINT, RESET)
+            } else {
+                String::from(" ")
+            };
+            let (text, _) = disassemble(memory, start);
+            if start == position {
+                writeln!(
+                    screen,
+                    "{}{}> {:>5}  {}{}",
+                    marker, CURRENT, start, text, RESET
+                )
+                .unwrap();
+            } else {
+                writeln!(screen, "{}  {:>5}  {}", marker, start, text).unwrap();
+            }
+        }
+        writeln!(screen).unwrap();
+
+        for (row, cells) in memory.chunks(10).enumerate() {
+            write!(screen, "{:>5}:", row * 10).unwrap();
+            for (column, value) in cells.iter().enumerate() {
+                let address = row * 10 + column;
+                let colour = if address == position {
+                    CURRENT
+                } else if self.recently_written.contains(&address) {
+                    WRITTEN
+                } else if self.watches.contains(&address) {
+                    BREAKPOINT
+                } else {
+                    ""
+                };
+                if colour.is_empty() {
+                    write!(screen, " {:>7}", value).unwrap();
+                } else {
+                    write!(screen, " {}{:>7}{}", colour, value, RESET).unwrap();
+                }
+            }
+            writeln!(screen).unwrap();
+        }
+        writeln!(screen).unwrap();
+
+        let input: Vec<String> = self
+            .computer
+            .pending_input()
+            .map(|x| x.to_string())
+            .collect();
+        let output: Vec<String> = self.computer.output.iter().map(|x| x.to_string()).collect();
+        writeln!(screen, "Input:  [{}]", input.join(", ")).unwrap();
+        writeln!(screen, "Output: [{}]", output.join(", ")).unwrap();
+        screen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::tests::WriteOnly;
+    use std::sync::{Arc, Mutex};
+
+    const PROGRAM: &str = "3,9,8,9,10,9,4,9,99,-1,8";
+
+    fn message(debugger: &mut Debugger, line: &str) -> String {
+        match debugger.execute(line).unwrap() {
+            Outcome::Continue(message) => message,
+            Outcome::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn step_and_feed_work() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![]));
+        message(&mut debugger, "step");
+        assert_eq!(debugger.computer.state, State::WaitingForInput);
+        message(&mut debugger, "feed 8");
+        message(&mut debugger, "step 2");
+        assert_eq!(debugger.computer.position(), 6);
+        assert_eq!(debugger.computer.memory()[9], 1);
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Computer is Terminated."
+        );
+        assert_eq!(debugger.computer.output, vec![1]);
+    }
+
+    #[test]
+    fn breakpoints_stop_continue() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![7]));
+        message(&mut debugger, "break 6");
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Breakpoint at 6 after 2 steps."
+        );
+        message(&mut debugger, "break 6");
+        message(&mut debugger, "set 9 5");
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Computer is Terminated."
+        );
+        assert_eq!(debugger.computer.output, vec![5]);
+    }
+
+    #[test]
+    fn watches_stop_continue() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![7]));
+        message(&mut debugger, "watch 9");
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Watched address 9 written with 7."
+        );
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Watched address 9 written with 0."
+        );
+    }
+
+    #[test]
+    fn watches_stop_on_unchanged_and_device_writes() {
+        // Writes 0 over the 0 at 9, then 5 to the device at 100.
+        let program = "1101,0,0,9,1101,2,3,100,99,0";
+        let mut computer = IntcodeComputer::new(program, vec![]);
+        let device = Arc::new(WriteOnly(Mutex::new(vec![0])));
+        computer.map_device(100..101, device.clone()).unwrap();
+        let mut debugger = Debugger::new(computer);
+        message(&mut debugger, "watch 9");
+        message(&mut debugger, "watch 100");
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Watched address 9 written with 0."
+        );
+        assert_eq!(
+            message(&mut debugger, "continue"),
+            "Watched address 100 written with 5."
+        );
+        assert_eq!(*device.0.lock().unwrap(), vec![5]);
+        assert!(debugger.render().contains("State: Running    IP: 8"));
+    }
+
+    #[test]
+    fn back_and_reverse_undo_steps() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![8]));
+        message(&mut debugger, "break 2");
+        message(&mut debugger, "step 4");
+        assert_eq!(debugger.computer.output, vec![1]);
+        assert_eq!(message(&mut debugger, "back 2"), "Undid 2 steps.");
+        assert!(debugger.computer.output.is_empty());
+        assert_eq!(
+            message(&mut debugger, "reverse"),
+            "Breakpoint at 2 after undoing 1 steps."
+        );
+        assert_eq!(debugger.computer.memory()[9], 8);
+        assert_eq!(
+            message(&mut debugger, "writer 9"),
+            "9 was last written by `in [9]` at 0 in step 0."
+        );
+        assert_eq!(
+            message(&mut debugger, "reverse"),
+            "Reached the start of the history after 1 steps."
+        );
+        assert_eq!(debugger.computer.memory()[9], -1);
+    }
+
+    #[test]
+    fn render_shows_listing_and_io() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![]));
+        message(&mut debugger, "feed 3 4");
+        message(&mut debugger, "step");
+        let screen = debugger.render();
+        assert!(screen.contains("State: Running    IP: 2"));
+        assert!(screen.contains("eq [9], [10], [9]"));
+        assert!(screen.contains(&format!("{}{:>7}{}", WRITTEN, 3, RESET)));
+        assert!(screen.contains("Input:  [4]"));
+        assert!(screen.contains("Output: []"));
+    }
+
+    #[test]
+    fn bad_commands_are_reported() {
+        let mut debugger = Debugger::new(IntcodeComputer::new(PROGRAM, vec![]));
+        assert!(debugger.execute("jump 3").is_err());
+        assert!(debugger.execute("set 1").is_err());
+        assert!(matches!(debugger.execute("quit"), Ok(Outcome::Quit)));
+    }
+}
@@ -0,0 +1,184 @@
+//! Memory-mapped devices.
+//!
+//! A device bound to a range of addresses handles every parameter read and
+//! every write to those addresses, instead of plain memory. Devices only see
+//! data accesses: instructions are always fetched from plain memory.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::ops::Range;
+
+/// Hardware behind a range of addresses. `offset` is relative to the start of
+/// the range. Devices are shared between clones of a computer, so any state
+/// they keep needs interior mutability.
+pub trait Device: Send + Sync {
+    fn read(&self, offset: usize) -> Result<i32, String>;
+    fn write(&self, offset: usize, value: i32) -> Result<(), String>;
+}
+
+#[derive(Clone)]
+pub(crate) struct Mapping {
+    addresses: Range<usize>,
+    device: Arc<dyn Device>,
+}
+
+impl Mapping {
+    pub(crate) fn new(addresses: Range<usize>, device: Arc<dyn Device>) -> Self {
+        Self { addresses, device }
+    }
+
+    pub(crate) fn overlaps(&self, addresses: &Range<usize>) -> bool {
+        self.addresses.start < addresses.end && addresses.start < self.addresses.end
+    }
+}
+
+/// Returns the device mapped at `address` along with the offset into it.
+pub(crate) fn find(devices: &[Mapping], address: usize) -> Option<(&dyn Device, usize)> {
+    devices
+        .iter()
+        .find(|mapping| mapping.addresses.contains(&address))
+        .map(|mapping| (&*mapping.device, address - mapping.addresses.start))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Engine, IntcodeComputer, State};
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+
+    /// Reads return the next reading; writes are ignored.
+    struct Sensor(Mutex<Vec<i32>>);
+
+    impl Device for Sensor {
+        fn read(&self, _offset: usize) -> Result<i32, String> {
+            self.0
+                .lock()
+                .unwrap()
+                .pop()
+                .ok_or_else(|| String::from("Sensor has no more readings"))
+        }
+
+        fn write(&self, _offset: usize, _value: i32) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    /// Remembers the last value written to each of its two registers.
+    #[derive(Default)]
+    struct Actuator([AtomicI32; 2]);
+
+    impl Device for Actuator {
+        fn read(&self, offset: usize) -> Result<i32, String> {
+            Ok(self.0[offset].load(Ordering::SeqCst))
+        }
+
+        fn write(&self, offset: usize, value: i32) -> Result<(), String> {
+            self.0[offset].store(value, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Records writes; reading is an error.
+    pub(crate) struct WriteOnly(pub(crate) Mutex<Vec<i32>>);
+
+    impl Device for WriteOnly {
+        fn read(&self, _offset: usize) -> Result<i32, String> {
+            Err(String::from("write-only"))
+        }
+
+        fn write(&self, offset: usize, value: i32) -> Result<(), String> {
+            self.0.lock().unwrap()[offset] = value;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn destinations_are_written_without_being_read() {
+        for program in [
+            "1101,2,3,100,99",
+            "1102,2,3,100,99",
+            "1107,2,3,100,99",
+            "1108,3,3,100,99",
+        ] {
+            let mut computer = IntcodeComputer::new(program, vec![]);
+            let device = Arc::new(WriteOnly(Mutex::new(vec![0])));
+            computer.map_device(100..101, device.clone()).unwrap();
+            assert_eq!(computer.run(), Ok(()), "{}", program);
+            assert_ne!(device.0.lock().unwrap()[0], 0, "{}", program);
+        }
+
+        // A sensor at the destination keeps all of its readings.
+        let mut computer = IntcodeComputer::new("1101,2,3,100,99", vec![]);
+        let sensor = Arc::new(Sensor(Mutex::new(vec![1, 2, 3])));
+        computer.map_device(100..101, sensor.clone()).unwrap();
+        computer.run().unwrap();
+        assert_eq!(*sensor.0.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn reading_a_write_only_device_fails() {
+        let mut computer = IntcodeComputer::new("1,100,0,0,99", vec![]);
+        computer
+            .map_device(100..101, Arc::new(WriteOnly(Mutex::new(vec![0]))))
+            .unwrap();
+        assert_eq!(computer.run(), Err(String::from("write-only")));
+    }
+
+    #[test]
+    fn opcodes_read_and_write_devices() {
+        // Adds two sensor readings into actuator register 1, then outputs it.
+        let mut computer = IntcodeComputer::new("1,100,100,201,4,201,99", vec![]);
+        let sensor = Arc::new(Sensor(Mutex::new(vec![30, 12])));
+        let actuator = Arc::new(Actuator::default());
+        computer.map_device(100..101, sensor).unwrap();
+        computer.map_device(200..202, actuator.clone()).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![42]);
+        assert_eq!(actuator.0[1].load(Ordering::SeqCst), 42);
+        assert_eq!(computer.state, State::Terminated);
+    }
+
+    #[test]
+    fn input_and_comparisons_write_devices() {
+        let mut computer = IntcodeComputer::new("3,200,1108,3,3,201,99", vec![17]);
+        let actuator = Arc::new(Actuator::default());
+        computer.map_device(200..202, actuator.clone()).unwrap();
+        computer.run().unwrap();
+        assert_eq!(actuator.0[0].load(Ordering::SeqCst), 17);
+        assert_eq!(actuator.0[1].load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn devices_shadow_plain_memory() {
+        let mut computer = IntcodeComputer::with_engine("4,5,99,0,0,7", vec![], Engine::Threaded);
+        let actuator = Arc::new(Actuator::default());
+        actuator.0[0].store(-5, Ordering::SeqCst);
+        computer.map_device(5..6, actuator).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![-5]);
+    }
+
+    #[test]
+    fn device_errors_propagate() {
+        let mut computer = IntcodeComputer::new("4,100,99", vec![]);
+        computer
+            .map_device(100..101, Arc::new(Sensor(Mutex::new(vec![]))))
+            .unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(String::from("Sensor has no more readings"))
+        );
+    }
+
+    #[test]
+    fn overlapping_devices_are_rejected() {
+        let mut computer = IntcodeComputer::new("99", vec![]);
+        computer
+            .map_device(10..20, Arc::new(Actuator::default()))
+            .unwrap();
+        assert!(computer
+            .map_device(19..21, Arc::new(Actuator::default()))
+            .is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Human readable listings of Intcode programs.
+//!
+//! Position-mode parameters are shown as `[address]` and immediate parameters
+//! as plain numbers, e.g. `add [9], 10, [3]`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+fn mnemonic(opcode: i32) -> Option<(&'static str, usize)> {
+    match opcode {
+        1 => Some(("add", 3)),
+        2 => Some(("mul", 3)),
+        3 => Some(("in", 1)),
+        4 => Some(("out", 1)),
+        5 => Some(("jnz", 2)),
+        6 => Some(("jz", 2)),
+        7 => Some(("lt", 3)),
+        8 => Some(("eq", 3)),
+        99 => Some(("halt", 0)),
+        _ => None,
+    }
+}
+
+/// Disassembles the instruction at `position`, returning its text and length.
+/// Cells that don't hold a valid instruction are shown as `data`.
+pub fn disassemble(memory: &[i32], position: usize) -> (String, usize) {
+    let num = memory[position];
+    let decoded = if num < 0 { None } else { mnemonic(num % 100) };
+    match decoded {
+        Some((name, num_operands)) if position + num_operands < memory.len() => {
+            let operands: Vec<String> = (0..num_operands)
+                .map(|i| {
+                    let operand = memory[position + 1 + i];
+                    match num / 10_i32.pow(i as u32 + 2) % 10 {
+                        0 => format!("[{}]", operand),
+                        1 => format!("{}", operand),
+                        mode => format!("?{}:{}", mode, operand),
+                    }
+                })
+                .collect();
+            if operands.is_empty() {
+                (String::from(name), 1)
+            } else {
+                (
+                    format!("{} {}", name, operands.join(", ")),
+                    1 + num_operands,
+                )
+            }
+        }
+        _ => (format!("data {}", num), 1),
+    }
+}
+
+/// Returns the positions of the instructions in `memory`, decoding linearly
+/// from 0 but resynchronising at `anchor` so that it always starts an instruction.
+pub fn instruction_starts(memory: &[i32], anchor: usize) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut position = 0;
+    while position < memory.len() {
+        if position < anchor && position + disassemble(memory, position).1 > anchor {
+            starts.push(position);
+            position = anchor;
+            continue;
+        }
+        starts.push(position);
+        position += disassemble(memory, position).1;
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_intcode;
+
+    #[test]
+    fn disassemble_works() {
+        let memory = parse_intcode("1002,4,3,4,33,99,-1");
+        assert_eq!(
+            disassemble(&memory, 0),
+            (String::from("mul [4], 3, [4]"), 4)
+        );
+        assert_eq!(disassemble(&memory, 4), (String::from("data 33"), 1));
+        assert_eq!(disassemble(&memory, 5), (String::from("halt"), 1));
+        assert_eq!(disassemble(&memory, 6), (String::from("data -1"), 1));
+    }
+
+    #[test]
+    fn instruction_starts_resynchronise_at_anchor() {
+        let memory = parse_intcode("1101,1,1,0,4,0,99");
+        assert_eq!(instruction_starts(&memory, 0), vec![0, 4, 6]);
+        assert_eq!(instruction_starts(&memory, 2), vec![0, 2, 6]);
+    }
+}
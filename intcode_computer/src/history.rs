@@ -0,0 +1,409 @@
+//! Reversible execution.
+//!
+//! While history is recorded, every instruction executed by `step` leaves an
+//! undo entry holding its position, its state and the cells it overwrote. Undo
+//! entries only reach back to the latest checkpoint, a copy of the machine taken
+//! every `checkpoint_interval` instructions. Stepping back past a checkpoint
+//! restores the one before it and replays forward, feeding the replay the input
+//! that was consumed the first time around, so memory use is bounded by the
+//! number of checkpoints kept.
+//!
+//! Replaying assumes that instructions are deterministic, so programs must not
+//! read memory-mapped devices while history is recorded. Writing memory,
+//! consuming input or producing output from outside `step` starts a fresh
+//! history.
+
+use crate::{IntcodeComputer, State};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// The instruction that last wrote a memory cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Writer {
+    /// How many instructions had been executed since recording started.
+    pub step: u64,
+    /// Position of the writing instruction.
+    pub position: usize,
+}
+
+#[derive(Clone)]
+struct Entry {
+    position: usize,
+    state: State,
+    /// Each overwritten cell with its previous value and writer, in write order.
+    writes: Vec<(usize, i32, Option<Writer>)>,
+    outputs: usize,
+}
+
+#[derive(Clone)]
+struct Checkpoint {
+    step: u64,
+    intcode: Vec<i32>,
+    position: usize,
+    output: Vec<i32>,
+    state: State,
+    writers: Vec<Option<Writer>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct History {
+    checkpoint_interval: u64,
+    max_checkpoints: usize,
+    step: u64,
+    writers: Vec<Option<Writer>>,
+    checkpoints: VecDeque<Checkpoint>,
+    /// Undo entries for the instructions executed since the latest checkpoint.
+    undo: Vec<Entry>,
+    /// The entry of the instruction being executed.
+    current: Option<Entry>,
+    /// Input consumed since the oldest checkpoint, with the step that consumed it.
+    consumed: Vec<(u64, i32)>,
+}
+
+impl History {
+    fn checkpoint(&mut self, computer: &IntcodeComputer) {
+        self.undo.clear();
+        self.checkpoints.push_back(Checkpoint {
+            step: self.step,
+            intcode: computer.intcode.clone(),
+            position: computer.position,
+            output: computer.output.clone(),
+            state: computer.state.clone(),
+            writers: self.writers.clone(),
+        });
+        if self.checkpoints.len() > self.max_checkpoints {
+            self.checkpoints.pop_front();
+            let oldest = self.checkpoints[0].step;
+            self.consumed.retain(|&(step, _)| step >= oldest);
+        }
+    }
+}
+
+impl IntcodeComputer {
+    /// Starts recording history from the current state, discarding any earlier
+    /// history. At most `checkpoint_interval * max_checkpoints` instructions
+    /// can be stepped back.
+    pub fn record_history(&mut self, checkpoint_interval: usize, max_checkpoints: usize) {
+        let mut history = History {
+            checkpoint_interval: checkpoint_interval.max(1) as u64,
+            max_checkpoints: max_checkpoints.max(1),
+            step: 0,
+            writers: vec![None; self.intcode.len()],
+            checkpoints: VecDeque::new(),
+            undo: Vec::new(),
+            current: None,
+            consumed: Vec::new(),
+        };
+        history.checkpoint(self);
+        self.history = Some(Box::new(history));
+    }
+
+    pub fn stop_recording_history(&mut self) {
+        self.history = None;
+    }
+
+    /// How many instructions can currently be stepped back.
+    pub fn history_len(&self) -> u64 {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.step - history.checkpoints[0].step)
+    }
+
+    /// The last recorded instruction that wrote `address`.
+    pub fn last_writer(&self, address: usize) -> Option<Writer> {
+        self.history
+            .as_ref()
+            .and_then(|history| history.writers.get(address).copied().flatten())
+    }
+
+    /// Undoes the last instruction. Returns false if there is no history left.
+    pub fn step_back(&mut self) -> bool {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return false,
+        };
+        let entry = match history.undo.pop() {
+            Some(entry) => entry,
+            None => return self.replay_to_previous_step(),
+        };
+        history.step -= 1;
+        for &(address, value, writer) in entry.writes.iter().rev() {
+            self.intcode[address] = value;
+            self.blocks.invalidate(address);
+            history.writers[address] = writer;
+        }
+        while let Some(&(step, value)) = history.consumed.last() {
+            if step != history.step {
+                break;
+            }
+            history.consumed.pop();
+            self.input.push_back(value);
+        }
+        self.output.truncate(self.output.len() - entry.outputs);
+        self.position = entry.position;
+        self.state = entry.state;
+        true
+    }
+
+    /// Steps back until the computer is at one of `breakpoints` or there is no
+    /// history left. Returns how many instructions were undone.
+    pub fn reverse_continue(&mut self, breakpoints: &[usize]) -> u64 {
+        let mut steps = 0;
+        while self.step_back() {
+            steps += 1;
+            if breakpoints.contains(&self.position) {
+                break;
+            }
+        }
+        steps
+    }
+
+    /// Restores the latest checkpoint before the current step and replays up to
+    /// the instruction before it.
+    fn replay_to_previous_step(&mut self) -> bool {
+        let history = self.history.as_mut().unwrap();
+        let target = match history.step.checked_sub(1) {
+            Some(target) => target,
+            None => return false,
+        };
+        let index = match history
+            .checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.step <= target)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        history.checkpoints.truncate(index + 1);
+        let checkpoint = history.checkpoints[index].clone();
+
+        // Each replayed instruction is handed exactly the input it consumed the
+        // first time around, so that it waits wherever it waited before.
+        let split = history
+            .consumed
+            .iter()
+            .position(|&(step, _)| step >= checkpoint.step)
+            .unwrap_or(history.consumed.len());
+        let mut replayed: VecDeque<(u64, i32)> = history.consumed.drain(split..).collect();
+        let pending = core::mem::take(&mut self.input);
+
+        history.step = checkpoint.step;
+        history.writers = checkpoint.writers;
+        history.undo.clear();
+        self.intcode = checkpoint.intcode;
+        self.position = checkpoint.position;
+        self.output = checkpoint.output;
+        self.state = checkpoint.state;
+        self.blocks.clear();
+        for step in checkpoint.step..target {
+            while let Some(&(consumed_at, value)) = replayed.front() {
+                if consumed_at != step {
+                    break;
+                }
+                replayed.pop_front();
+                self.input.push_front(value);
+            }
+            // Errors are replayed just like they happened the first time.
+            let _ = self.step();
+        }
+        self.input = pending;
+        for (_, value) in replayed.into_iter().rev() {
+            self.input.push_back(value);
+        }
+        true
+    }
+
+    pub(crate) fn begin_step(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.current = Some(Entry {
+                position: self.position,
+                state: self.state.clone(),
+                writes: Vec::new(),
+                outputs: 0,
+            });
+        }
+    }
+
+    pub(crate) fn end_step(&mut self) {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return,
+        };
+        if let Some(entry) = history.current.take() {
+            history.undo.push(entry);
+            history.step += 1;
+            if history.step % history.checkpoint_interval == 0 {
+                let mut history = self.history.take().unwrap();
+                history.checkpoint(self);
+                self.history = Some(history);
+            }
+        }
+    }
+
+    /// The address and current value of the cell `idx`, if a write to it has
+    /// to be recorded.
+    pub(crate) fn recorded_cell(&self, idx: i32) -> Option<(usize, i32)> {
+        self.history.as_ref()?;
+        let address = usize::try_from(idx).ok()?;
+        if crate::devices::find(&self.devices, address).is_some() {
+            return None;
+        }
+        Some((address, *self.intcode.get(address)?))
+    }
+
+    pub(crate) fn record_write(&mut self, address: usize, previous: i32) {
+        let history = self.history.as_mut().unwrap();
+        match &mut history.current {
+            Some(entry) => {
+                entry
+                    .writes
+                    .push((address, previous, history.writers[address]));
+                history.writers[address] = Some(Writer {
+                    step: history.step,
+                    position: entry.position,
+                });
+            }
+            None => self.restart_history(),
+        }
+    }
+
+    pub(crate) fn record_input(&mut self, value: i32) {
+        if let Some(history) = &mut self.history {
+            if history.current.is_some() {
+                history.consumed.push((history.step, value));
+            } else {
+                self.restart_history();
+            }
+        }
+    }
+
+    pub(crate) fn record_output(&mut self) {
+        if let Some(history) = &mut self.history {
+            match &mut history.current {
+                Some(entry) => entry.outputs += 1,
+                None => self.restart_history(),
+            }
+        }
+    }
+
+    fn restart_history(&mut self) {
+        if let Some(history) = &self.history {
+            let (interval, max_checkpoints) =
+                (history.checkpoint_interval, history.max_checkpoints);
+            self.record_history(interval as usize, max_checkpoints);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reference::tests::{random_program, Rng};
+    use crate::{read_file, IntcodeComputer, State};
+
+    /// Everything a step back has to restore.
+    fn observe(computer: &IntcodeComputer) -> (Vec<i32>, usize, Vec<i32>, Vec<i32>, State) {
+        (
+            computer.memory().to_vec(),
+            computer.position(),
+            computer.pending_input().copied().collect(),
+            computer.output.clone(),
+            computer.state.clone(),
+        )
+    }
+
+    /// Records every state on the way forward and checks that stepping back
+    /// visits them again in reverse.
+    fn assert_reversible(raw_code: &str, input: Vec<i32>, interval: usize, max_steps: usize) {
+        let mut computer = IntcodeComputer::new(raw_code, input);
+        computer.record_history(interval, usize::MAX);
+        let mut states = vec![observe(&computer)];
+        for _ in 0..max_steps {
+            if computer.step().is_err() {
+                states.push(observe(&computer));
+                break;
+            }
+            states.push(observe(&computer));
+            if computer.state != State::Running {
+                break;
+            }
+        }
+        states.pop();
+        while let Some(expected) = states.pop() {
+            assert!(computer.step_back(), "{}", raw_code);
+            assert_eq!(observe(&computer), expected, "{}", raw_code);
+        }
+        assert!(!computer.step_back());
+    }
+
+    #[test]
+    fn step_back_restores_every_state() {
+        let day5 = read_file("../day5/input");
+        for &interval in [1, 7, 1_000].iter() {
+            assert_reversible(&day5, vec![5], interval, 100_000);
+            assert_reversible("3,9,8,9,10,9,4,9,99,-1,8", vec![8], interval, 100);
+        }
+    }
+
+    #[test]
+    fn random_programs_are_reversible() {
+        let mut rng = Rng(0x0bac_c0de);
+        for _ in 0..1_000 {
+            let program = random_program(&mut rng);
+            let input = (0..rng.below(4)).map(|_| rng.below(21) - 10).collect();
+            assert_reversible(&program, input, 1 + rng.below(5) as usize, 200);
+        }
+    }
+
+    #[test]
+    fn replay_keeps_input_fed_later() {
+        let mut computer = IntcodeComputer::new("3,0,3,1,4,0,4,1,99", vec![]);
+        computer.record_history(2, 10);
+        computer.run().unwrap();
+        computer.feed_input(7).unwrap();
+        computer.feed_input(8).unwrap();
+        assert_eq!(computer.output, vec![7, 8]);
+        assert_eq!(computer.reverse_continue(&[2]), 4);
+        assert_eq!(computer.position(), 2);
+        assert_eq!(computer.pending_input().collect::<Vec<_>>(), vec![&8]);
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![7, 8]);
+    }
+
+    #[test]
+    fn last_writer_finds_instruction() {
+        let mut computer = IntcodeComputer::new("1101,2,3,9,1002,9,4,9,99,0", vec![]);
+        computer.record_history(100, 1);
+        computer.run().unwrap();
+        let writer = computer.last_writer(9).unwrap();
+        assert_eq!((writer.step, writer.position), (1, 4));
+        computer.step_back();
+        computer.step_back();
+        assert_eq!(computer.last_writer(9).unwrap().position, 0);
+        assert_eq!(computer.last_writer(3), None);
+    }
+
+    #[test]
+    fn history_is_bounded_by_checkpoints() {
+        let mut computer = IntcodeComputer::new(&read_file("../day5/input"), vec![5]);
+        computer.record_history(10, 3);
+        computer.run().unwrap();
+        let len = computer.history_len();
+        assert!((20..30).contains(&len));
+        assert_eq!(computer.reverse_continue(&[]), len);
+        assert!(!computer.step_back());
+    }
+
+    #[test]
+    fn external_writes_restart_history() {
+        let mut computer = IntcodeComputer::new("1101,2,3,9,1002,9,4,9,99,0", vec![]);
+        computer.record_history(100, 1);
+        computer.step().unwrap();
+        computer.set_memory(9, 1).unwrap();
+        assert_eq!(computer.history_len(), 0);
+        assert!(!computer.step_back());
+        computer.run().unwrap();
+        assert_eq!(computer.memory()[9], 4);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{IntcodeComputer, Opcode, Operation};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+
+/// Executes a decoded instruction. Handlers get the decoded operands through
+/// `Opcode` and the machine's memory, I/O and position through
+/// `IntcodeComputer`, and describe the resulting write, jump, wait or halt
+/// through the returned `Operation`.
+pub type Handler =
+    Arc<dyn Fn(&Opcode, &mut IntcodeComputer) -> Result<Operation, String> + Send + Sync>;
+
+/// The table of opcodes a computer understands, along with the number of
+/// operands each one takes.
+#[derive(Clone)]
+pub struct InstructionSet {
+    instructions: BTreeMap<i32, (usize, Handler)>,
+    /// Whether this is still the untouched default instruction set.
+    default: bool,
+}
+
+impl InstructionSet {
+    /// An instruction set without any opcodes, not even 99.
+    pub fn empty() -> Self {
+        Self {
+            instructions: BTreeMap::new(),
+            default: false,
+        }
+    }
+
+    /// Registers `opcode`, replacing any existing instruction with the same opcode.
+    pub fn register<F>(&mut self, opcode: i32, num_operands: usize, handler: F) -> &mut Self
+    where
+        F: Fn(&Opcode, &mut IntcodeComputer) -> Result<Operation, String> + Send + Sync + 'static,
+    {
+        self.instructions
+            .insert(opcode, (num_operands, Arc::new(handler)));
+        self.default = false;
+        self
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    pub fn num_operands(&self, opcode: i32) -> Option<usize> {
+        self.instructions
+            .get(&opcode)
+            .map(|&(num_operands, _)| num_operands)
+    }
+
+    /// Returns the handler for `opcode`, e.g. to wrap it with instrumentation.
+    pub fn handler(&self, opcode: i32) -> Option<Handler> {
+        self.instructions
+            .get(&opcode)
+            .map(|(_, handler)| Arc::clone(handler))
+    }
+}
+
+impl Default for InstructionSet {
+    /// The opcodes from day 2 and day 5.
+    fn default() -> Self {
+        let mut instruction_set = Self::empty();
+        instruction_set
+            .register(1, 3, Opcode::opcode_1)
+            .register(2, 3, Opcode::opcode_2)
+            .register(3, 1, Opcode::opcode_3)
+            .register(4, 1, Opcode::opcode_4)
+            .register(5, 2, Opcode::opcode_5)
+            .register(6, 2, Opcode::opcode_6)
+            .register(7, 3, Opcode::opcode_7)
+            .register(8, 3, Opcode::opcode_8)
+            .register(99, 0, Opcode::opcode_99);
+        instruction_set.default = true;
+        instruction_set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn custom_opcode_works() {
+        let mut instruction_set = InstructionSet::default();
+        // 10: store the sum of the remaining input in the given address.
+        instruction_set.register(10, 1, |opcode, computer| {
+            let mut sum = 0;
+            while let Some(input) = computer.consume_input() {
+                sum += input;
+            }
+            Ok(Operation {
+                set_value: Some((opcode.operands()[0], sum)),
+                ..Default::default()
+            })
+        });
+        let mut computer = IntcodeComputer::with_instruction_set(
+            "10,0,4,0,99",
+            vec![1, 2, 3],
+            Arc::new(instruction_set),
+        );
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![6]);
+    }
+
+    #[test]
+    fn handlers_can_be_wrapped() {
+        let executed = Arc::new(AtomicUsize::new(0));
+        let mut instruction_set = InstructionSet::default();
+        let add = instruction_set.handler(1).unwrap();
+        let counter = Arc::clone(&executed);
+        instruction_set.register(1, 3, move |opcode, computer| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            add(opcode, computer)
+        });
+        let mut computer = IntcodeComputer::with_instruction_set(
+            "1101,1,1,0,1,0,0,0,4,0,99",
+            Vec::new(),
+            Arc::new(instruction_set),
+        );
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![4]);
+        assert_eq!(executed.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn unregistered_opcode_errors() {
+        let mut computer = IntcodeComputer::with_instruction_set(
+            "4,0,99",
+            Vec::new(),
+            Arc::new(InstructionSet::empty()),
+        );
+        assert_eq!(computer.run(), Err(String::from("Unknown opcode: 4")));
+    }
+}
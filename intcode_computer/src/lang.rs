@@ -0,0 +1,192 @@
+//! A tiny language that compiles to Intcode, for writing test programs.
+//!
+//! ```text
+//! n = input();
+//! total = 0;
+//! while n > 0 {
+//!     total = total + n * n;
+//!     n = n - 1;
+//! }
+//! if total >= 100 { output(total); } else { output(-1); }
+//! ```
+//!
+//! Values are `i32`s. Expressions support `+`, `-`, `*`, unary `-`, the
+//! comparisons `==`, `!=`, `<`, `>`, `<=` and `>=` (which evaluate to 1 or 0),
+//! parentheses, variables, integer literals and `input()`. Statements are
+//! assignments, `output(expr);`, `if`/`else` and `while`. Conditions are true
+//! when non-zero, and `//` starts a comment.
+//!
+//! Programs only use opcodes 1 to 8 and 99, so they run on any `IntcodeComputer`.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+mod codegen;
+mod parser;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Number(i32),
+    Variable(String),
+    Input,
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Statement {
+    Assign(String, Expr),
+    Output(Expr),
+    If(Expr, Vec<Statement>, Vec<Statement>),
+    While(Expr, Vec<Statement>),
+}
+
+/// Compiles `source` into a comma separated Intcode program, ready for
+/// `IntcodeComputer::new`. Errors mention the line they occurred on.
+pub fn compile(source: &str) -> Result<String, String> {
+    let program = parser::parse(source)?;
+    Ok(codegen::generate(&program)
+        .iter()
+        .map(|x| format!("{}", x))
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntcodeComputer, State};
+
+    fn run(source: &str, input: Vec<i32>) -> Vec<i32> {
+        let raw_code = compile(source).unwrap();
+        let mut computer = IntcodeComputer::new(&raw_code, input);
+        computer.run().unwrap();
+        assert_eq!(computer.state, State::Terminated);
+        computer.output
+    }
+
+    #[test]
+    fn arithmetic_works() {
+        assert_eq!(run("output(1 + 2 * 3);", vec![]), vec![7]);
+        assert_eq!(run("output((1 + 2) * 3);", vec![]), vec![9]);
+        assert_eq!(run("output(10 - 4 - 3);", vec![]), vec![3]);
+        assert_eq!(run("output(-(2 - 7) * -2);", vec![]), vec![-10]);
+        assert_eq!(
+            run("x = input(); y = input(); output(x - y);", vec![3, 10]),
+            vec![-7]
+        );
+    }
+
+    #[test]
+    fn comparisons_work() {
+        let source = "a = input(); b = input();
+            output(a == b); output(a != b); output(a < b);
+            output(a > b); output(a <= b); output(a >= b);";
+        assert_eq!(run(source, vec![1, 2]), vec![0, 1, 1, 0, 1, 0]);
+        assert_eq!(run(source, vec![2, 2]), vec![1, 0, 0, 0, 1, 1]);
+        assert_eq!(run(source, vec![3, 2]), vec![0, 1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn if_else_works() {
+        let source = "
+            x = input();
+            if x < 8 {
+                output(999);
+            } else if x == 8 {
+                output(1000);
+            } else {
+                output(1001);
+            }";
+        assert_eq!(run(source, vec![7]), vec![999]);
+        assert_eq!(run(source, vec![8]), vec![1000]);
+        assert_eq!(run(source, vec![9]), vec![1001]);
+    }
+
+    #[test]
+    fn while_works() {
+        let source = "
+            // Sums the squares of 1..=n.
+            n = input();
+            total = 0;
+            while n > 0 {
+                total = total + n * n;
+                n = n - 1;
+            }
+            output(total);";
+        assert_eq!(run(source, vec![0]), vec![0]);
+        assert_eq!(run(source, vec![3]), vec![14]);
+        assert_eq!(run(source, vec![10]), vec![385]);
+    }
+
+    #[test]
+    fn input_echo_until_zero() {
+        let source = "x = input(); while x { output(x); x = input(); }";
+        assert_eq!(run(source, vec![4, -2, 9, 0]), vec![4, -2, 9]);
+    }
+
+    #[test]
+    fn compiled_programs_pause_for_input() {
+        let raw_code = compile("output(input() + input());").unwrap();
+        let mut computer = IntcodeComputer::new(&raw_code, vec![]);
+        computer.run().unwrap();
+        assert_eq!(computer.state, State::WaitingForInput);
+        computer.feed_input(20).unwrap();
+        computer.feed_input(22).unwrap();
+        assert_eq!(computer.output, vec![42]);
+    }
+
+    #[test]
+    fn compiled_fibonacci_matches_reference() {
+        let source = "
+            n = input();
+            a = 0;
+            b = 1;
+            while n > 0 {
+                output(a);
+                next = a + b;
+                a = b;
+                b = next;
+                n = n - 1;
+            }";
+        let raw_code = compile(source).unwrap();
+        let mut reference = crate::reference::ReferenceComputer::new(&raw_code, vec![10]);
+        reference.run().unwrap();
+        assert_eq!(reference.output, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+        assert_eq!(run(source, vec![10]), reference.output);
+    }
+
+    #[test]
+    fn errors_mention_line() {
+        assert_eq!(
+            compile("x = 1;\noutput(y);"),
+            Err(String::from("line 2: undefined variable `y`"))
+        );
+        assert_eq!(
+            compile("x = 1\noutput(x);"),
+            Err(String::from("line 2: expected `;`, found `output`"))
+        );
+        assert_eq!(
+            compile("x = 1 / 2;"),
+            Err(String::from("line 1: unexpected character `/`"))
+        );
+        assert_eq!(
+            compile("while 1 {"),
+            Err(String::from("line 1: expected `}`, found end of input"))
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use super::{BinaryOp, Expr, Statement};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// An operand whose final value is only known once the whole program has been
+/// laid out.
+#[derive(Clone, Copy)]
+enum Operand {
+    Immediate(i32),
+    /// Address of a variable.
+    Variable(usize),
+    /// Address of a temporary.
+    Temporary(usize),
+    /// Position of a label, as an immediate.
+    Label(usize),
+}
+
+impl Operand {
+    fn mode(self) -> i32 {
+        match self {
+            Operand::Immediate(_) | Operand::Label(_) => 1,
+            Operand::Variable(_) | Operand::Temporary(_) => 0,
+        }
+    }
+}
+
+struct Generator {
+    /// Opcodes and operands in program order.
+    code: Vec<Result<i32, Operand>>,
+    /// Position of each label, once it has been placed.
+    labels: Vec<Option<usize>>,
+    variables: BTreeMap<String, usize>,
+    temporaries: usize,
+    max_temporaries: usize,
+}
+
+impl Generator {
+    /// Emits an instruction, adding parameter modes to `opcode`.
+    fn emit(&mut self, opcode: i32, operands: &[Operand]) {
+        let modes = operands
+            .iter()
+            .rev()
+            .fold(0, |modes, operand| modes * 10 + operand.mode());
+        self.code.push(Ok(opcode + modes * 100));
+        self.code
+            .extend(operands.iter().map(|&operand| Err(operand)));
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn temporary(&mut self) -> Operand {
+        self.temporaries += 1;
+        self.max_temporaries = self.max_temporaries.max(self.temporaries);
+        Operand::Temporary(self.temporaries - 1)
+    }
+
+    fn variable(&mut self, name: &str) -> Operand {
+        let next = self.variables.len();
+        Operand::Variable(*self.variables.entry(String::from(name)).or_insert(next))
+    }
+
+    /// Emits code evaluating `expr`, returning where to find its value.
+    fn expression(&mut self, expr: &Expr) -> Operand {
+        match expr {
+            Expr::Number(n) => Operand::Immediate(*n),
+            Expr::Variable(name) => self.variable(name),
+            Expr::Input => {
+                let result = self.temporary();
+                self.emit(3, &[result]);
+                result
+            }
+            Expr::Negate(inner) => match self.expression(inner) {
+                Operand::Immediate(n) if n != i32::MIN => Operand::Immediate(-n),
+                value => {
+                    let result = self.temporary();
+                    self.emit(2, &[value, Operand::Immediate(-1), result]);
+                    result
+                }
+            },
+            Expr::Binary(op, left, right) => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                let result = self.temporary();
+                match op {
+                    BinaryOp::Add => self.emit(1, &[left, right, result]),
+                    BinaryOp::Subtract => {
+                        self.emit(2, &[right, Operand::Immediate(-1), result]);
+                        self.emit(1, &[left, result, result]);
+                    }
+                    BinaryOp::Multiply => self.emit(2, &[left, right, result]),
+                    BinaryOp::Less => self.emit(7, &[left, right, result]),
+                    BinaryOp::Greater => self.emit(7, &[right, left, result]),
+                    BinaryOp::Equal => self.emit(8, &[left, right, result]),
+                    BinaryOp::NotEqual => {
+                        self.emit(8, &[left, right, result]);
+                        self.emit(8, &[result, Operand::Immediate(0), result]);
+                    }
+                    BinaryOp::LessOrEqual => {
+                        self.emit(7, &[right, left, result]);
+                        self.emit(8, &[result, Operand::Immediate(0), result]);
+                    }
+                    BinaryOp::GreaterOrEqual => {
+                        self.emit(7, &[left, right, result]);
+                        self.emit(8, &[result, Operand::Immediate(0), result]);
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+            // Temporaries never outlive the statement that needed them.
+            self.temporaries = 0;
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign(name, value) => {
+                let value = self.expression(value);
+                let variable = self.variable(name);
+                self.emit(1, &[value, Operand::Immediate(0), variable]);
+            }
+            Statement::Output(value) => {
+                let value = self.expression(value);
+                self.emit(4, &[value]);
+            }
+            Statement::If(condition, then, otherwise) => {
+                let else_label = self.new_label();
+                let end_label = self.new_label();
+                let condition = self.expression(condition);
+                self.emit(6, &[condition, Operand::Label(else_label)]);
+                self.statements(then);
+                self.emit(5, &[Operand::Immediate(1), Operand::Label(end_label)]);
+                self.place_label(else_label);
+                self.statements(otherwise);
+                self.place_label(end_label);
+            }
+            Statement::While(condition, body) => {
+                let top_label = self.new_label();
+                let end_label = self.new_label();
+                self.place_label(top_label);
+                let condition = self.expression(condition);
+                self.emit(6, &[condition, Operand::Label(end_label)]);
+                self.statements(body);
+                self.emit(5, &[Operand::Immediate(1), Operand::Label(top_label)]);
+                self.place_label(end_label);
+            }
+        }
+    }
+}
+
+/// Lays out the program followed by a halt, then the variables, then the
+/// temporaries.
+pub(super) fn generate(program: &[Statement]) -> Vec<i32> {
+    let mut generator = Generator {
+        code: Vec::new(),
+        labels: Vec::new(),
+        variables: BTreeMap::new(),
+        temporaries: 0,
+        max_temporaries: 0,
+    };
+    generator.statements(program);
+    generator.emit(99, &[]);
+
+    let variables_start = generator.code.len();
+    let temporaries_start = variables_start + generator.variables.len();
+    let data_cells = generator.variables.len() + generator.max_temporaries;
+    let labels = generator.labels;
+    generator
+        .code
+        .into_iter()
+        .map(|item| match item {
+            Ok(value) => value,
+            Err(Operand::Immediate(value)) => value,
+            Err(Operand::Variable(index)) => (variables_start + index) as i32,
+            Err(Operand::Temporary(index)) => (temporaries_start + index) as i32,
+            Err(Operand::Label(label)) => labels[label].unwrap() as i32,
+        })
+        .chain(core::iter::repeat_n(0, data_cells))
+        .collect()
+}
@@ -0,0 +1,282 @@
+use super::{BinaryOp, Expr, Statement};
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i32),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "(", ")", "{", "}", ";",
+];
+
+fn describe(token: Option<&(Token, usize)>) -> String {
+    match token {
+        Some((Token::Number(n), _)) => format!("`{}`", n),
+        Some((Token::Identifier(name), _)) => format!("`{}`", name),
+        Some((Token::Symbol(symbol), _)) => format!("`{}`", symbol),
+        None => String::from("end of input"),
+    }
+}
+
+/// Splits `source` into tokens, each paired with its line number.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split("//").next().unwrap_or("");
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+            let length = if c.is_ascii_digit() {
+                let length = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let number = rest[..length].parse().map_err(|_| {
+                    format!(
+                        "line {}: number `{}` is too large",
+                        line_number,
+                        &rest[..length]
+                    )
+                })?;
+                tokens.push((Token::Number(number), line_number));
+                length
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let length = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((
+                    Token::Identifier(String::from(&rest[..length])),
+                    line_number,
+                ));
+                length
+            } else if let Some(&symbol) = SYMBOLS.iter().find(|&&symbol| rest.starts_with(symbol)) {
+                tokens.push((Token::Symbol(symbol), line_number));
+                symbol.len()
+            } else {
+                return Err(format!(
+                    "line {}: unexpected character `{}`",
+                    line_number, c
+                ));
+            };
+            rest = rest[length..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Variables assigned so far, to reject reads before the first assignment.
+    /// Slots are allocated by the code generator, not from this set.
+    variables: BTreeSet<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |&(_, line)| line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line(), message))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(name)) => name == keyword,
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.is_symbol(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(format!(
+                "expected `{}`, found {}",
+                symbol,
+                describe(self.tokens.get(self.position))
+            ))
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, String> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while !self.is_symbol("}") {
+            if self.peek().is_none() {
+                return self.expect("}").map(|_| statements);
+            }
+            statements.push(self.statement()?);
+        }
+        self.expect("}")?;
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.is_keyword("if") {
+            self.position += 1;
+            let condition = self.expression()?;
+            let then = self.block()?;
+            let otherwise = if self.is_keyword("else") {
+                self.position += 1;
+                if self.is_keyword("if") {
+                    vec![self.statement()?]
+                } else {
+                    self.block()?
+                }
+            } else {
+                Vec::new()
+            };
+            Ok(Statement::If(condition, then, otherwise))
+        } else if self.is_keyword("while") {
+            self.position += 1;
+            let condition = self.expression()?;
+            Ok(Statement::While(condition, self.block()?))
+        } else if self.is_keyword("output") {
+            self.position += 1;
+            self.expect("(")?;
+            let value = self.expression()?;
+            self.expect(")")?;
+            self.expect(";")?;
+            Ok(Statement::Output(value))
+        } else if let Some(Token::Identifier(name)) = self.peek().cloned() {
+            if is_reserved(&name) {
+                return self.error(format!("`{}` cannot be assigned to", name));
+            }
+            self.position += 1;
+            self.expect("=")?;
+            let value = self.expression()?;
+            self.expect(";")?;
+            self.variables.insert(name.clone());
+            Ok(Statement::Assign(name, value))
+        } else {
+            self.error(format!(
+                "expected a statement, found {}",
+                describe(self.tokens.get(self.position))
+            ))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        let op = match self.peek() {
+            Some(Token::Symbol("==")) => BinaryOp::Equal,
+            Some(Token::Symbol("!=")) => BinaryOp::NotEqual,
+            Some(Token::Symbol("<")) => BinaryOp::Less,
+            Some(Token::Symbol(">")) => BinaryOp::Greater,
+            Some(Token::Symbol("<=")) => BinaryOp::LessOrEqual,
+            Some(Token::Symbol(">=")) => BinaryOp::GreaterOrEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.sum()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => BinaryOp::Add,
+                Some(Token::Symbol("-")) => BinaryOp::Subtract,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.product()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.is_symbol("*") {
+            self.position += 1;
+            let right = self.unary()?;
+            left = Expr::Binary(BinaryOp::Multiply, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.is_symbol("-") {
+            self.position += 1;
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Identifier(name)) if name == "input" => {
+                self.expect("(")?;
+                self.expect(")")?;
+                Ok(Expr::Input)
+            }
+            Some(Token::Identifier(name)) if !is_reserved(&name) => {
+                if self.variables.contains(&name) {
+                    Ok(Expr::Variable(name))
+                } else {
+                    Err(format!("line {}: undefined variable `{}`", line, name))
+                }
+            }
+            token => {
+                self.position -= 1;
+                let found = describe(token.map(|token| (token, line)).as_ref());
+                self.error(format!("expected an expression, found {}", found))
+            }
+        }
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    ["if", "else", "while", "input", "output"].contains(&name)
+}
+
+pub(super) fn parse(source: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+        variables: BTreeSet::new(),
+    };
+    let mut statements = Vec::new();
+    while parser.peek().is_some() {
+        statements.push(parser.statement()?);
+    }
+    Ok(statements)
+}
@@ -1,23 +1,55 @@
-use std::cell::RefCell;
-use std::cmp::PartialEq;
-use std::collections::VecDeque;
-use std::convert::TryFrom;
-use std::default::Default;
-use std::{fs, iter};
+//! An Intcode virtual machine.
+//!
+//! The VM itself only needs `alloc`. File access lives behind the default `std`
+//! feature, and the `wasm-bindgen` feature exposes the VM to JavaScript.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(test)]
+extern crate self as intcode_computer;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::PartialEq;
+use core::convert::TryFrom;
+use core::default::Default;
+use core::iter;
+use core::ops::Range;
+
+pub mod aot;
+#[cfg(feature = "std")]
+pub mod debugger;
+mod devices;
+pub mod disassemble;
+#[cfg(feature = "std")]
+mod files;
+mod history;
+mod instruction_set;
+pub mod lang;
+pub mod reference;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+mod threaded;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+
+pub use devices::Device;
+#[cfg(feature = "std")]
+pub use files::read_file;
+pub use history::Writer;
+pub use instruction_set::{Handler, InstructionSet};
 
 pub fn parse_intcode(code: &str) -> Vec<i32> {
     code.split(',').map(|item| item.parse().unwrap()).collect()
 }
 
-pub fn read_file(path: &str) -> String {
-    fs::read_to_string(path)
-        .unwrap()
-        .chars()
-        .filter(|&c| c.is_digit(10) || c == ',' || c == '-')
-        .collect()
-}
-
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum State {
     WaitingForInput,
     Terminated,
@@ -25,49 +57,167 @@ pub enum State {
     Initialized,
 }
 
+/// How `IntcodeComputer::run` executes a program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    /// Decodes and executes one instruction at a time.
+    Interpreter,
+    /// Executes cached, pre-decoded basic blocks. Computers with custom opcodes,
+    /// memory-mapped devices or recorded history are always interpreted.
+    Threaded,
+}
+
+#[derive(Clone)]
 pub struct IntcodeComputer {
     intcode: Vec<i32>,
     position: usize,
     input: VecDeque<i32>,
     pub output: Vec<i32>,
     pub state: State,
+    instruction_set: Arc<InstructionSet>,
+    engine: Engine,
+    blocks: threaded::BlockCache,
+    devices: Vec<devices::Mapping>,
+    history: Option<Box<history::History>>,
 }
 
 impl IntcodeComputer {
     pub fn new(raw_intcode: &str, input: Vec<i32>) -> Self {
+        Self::with_instruction_set(raw_intcode, input, Arc::new(InstructionSet::default()))
+    }
+
+    pub fn with_instruction_set(
+        raw_intcode: &str,
+        input: Vec<i32>,
+        instruction_set: Arc<InstructionSet>,
+    ) -> Self {
         Self {
             intcode: parse_intcode(raw_intcode),
             input: input.iter().rev().copied().collect(),
             output: Vec::new(),
             position: 0,
             state: State::Initialized,
+            instruction_set,
+            engine: Engine::Interpreter,
+            blocks: Default::default(),
+            devices: Vec::new(),
+            history: None,
         }
     }
 
-    pub fn run(&mut self) -> Result<(), String> {
+    pub fn with_engine(raw_intcode: &str, input: Vec<i32>, engine: Engine) -> Self {
+        Self {
+            engine,
+            ..Self::new(raw_intcode, input)
+        }
+    }
+
+    pub fn memory(&self) -> &[i32] {
+        &self.intcode
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Binds `addresses` to `device`, which then handles all parameter reads
+    /// and writes to them. Addresses may lie beyond the end of the program.
+    pub fn map_device(
+        &mut self,
+        addresses: Range<usize>,
+        device: Arc<dyn Device>,
+    ) -> Result<(), String> {
+        if self
+            .devices
+            .iter()
+            .any(|mapping| mapping.overlaps(&addresses))
+        {
+            return Err(format!(
+                "Addresses {:?} overlap an existing device.",
+                addresses
+            ));
+        }
+        self.devices.push(devices::Mapping::new(addresses, device));
+        Ok(())
+    }
+
+    pub fn get_memory(&self, idx: i32) -> Result<i32, String> {
+        get_or_error(&self.intcode, &self.devices, idx)
+    }
+
+    pub fn set_memory(&mut self, idx: i32, val: i32) -> Result<(), String> {
+        let recorded = self.recorded_cell(idx);
+        set_or_error(&mut self.intcode, &self.devices, idx, val)?;
+        self.blocks.invalidate(idx as usize);
+        if let Some((address, previous)) = recorded {
+            self.record_write(address, previous);
+        }
+        Ok(())
+    }
+
+    /// Executes the single instruction at the current position.
+    pub fn step(&mut self) -> Result<(), String> {
+        self.traced_step().map(|_| ())
+    }
+
+    /// Like `step`, but also returns the address and value the instruction
+    /// wrote, if it wrote anything. Writes to devices and writes that leave a
+    /// cell unchanged are reported too.
+    pub fn traced_step(&mut self) -> Result<Option<(usize, i32)>, String> {
+        self.begin_step();
+        let result = self.execute_step();
+        self.end_step();
+        result
+    }
+
+    fn execute_step(&mut self) -> Result<Option<(usize, i32)>, String> {
         self.state = State::Running;
-        while self.position < self.intcode.len() && self.state == State::Running {
-            let operation = Opcode::new(&self.intcode, self.position);
-            if operation.opcode == 99 {
-                self.state = State::Terminated;
-            } else {
-                operation.execute(self)?;
-            }
+        if self.position >= self.intcode.len() {
+            return Err(String::from("EOF error"));
+        }
+        let operation = Opcode::new(&self.intcode, self.position, &self.instruction_set)?;
+        operation.traced_execute(self)
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        if self.engine == Engine::Threaded
+            && self.instruction_set.is_default()
+            && self.devices.is_empty()
+            && self.history.is_none()
+        {
+            return threaded::run(self);
+        }
+        self.step()?;
+        while self.state == State::Running {
+            self.step()?;
         }
 
         match self.state {
-            State::Running => Err(String::from("EOF error")),
             State::WaitingForInput | State::Terminated => Ok(()),
             _ => Err(String::from("This shouldn't have happened.")),
         }
     }
 
     pub fn consume_input(&mut self) -> Option<i32> {
-        self.input.pop_back()
+        let input = self.input.pop_back();
+        if let Some(value) = input {
+            self.record_input(value);
+        }
+        input
     }
 
-    pub fn feed_input(&mut self, input: i32) -> Result<(), String> {
+    /// Pending input, in the order it will be consumed.
+    pub fn pending_input(&self) -> impl Iterator<Item = &i32> {
+        self.input.iter().rev()
+    }
+
+    /// Queues `input` without resuming a computer that is waiting for input.
+    pub fn queue_input(&mut self, input: i32) {
         self.input.push_front(input);
+    }
+
+    pub fn feed_input(&mut self, input: i32) -> Result<(), String> {
+        self.queue_input(input);
         match self.state {
             State::Terminated => Err(String::from(
                 "Attempted to feed input to terminated computer.",
@@ -80,13 +230,16 @@ impl IntcodeComputer {
         }
     }
 
-    fn produce_output(&mut self, output: i32) -> Result<(), String> {
+    pub fn produce_output(&mut self, output: i32) -> Result<(), String> {
         self.output.push(output);
+        self.record_output();
         Ok(())
     }
 }
 
-struct Opcode {
+/// A decoded instruction: the opcode together with its raw operands and their
+/// parameter modes.
+pub struct Opcode {
     pub opcode: i32,
     operands: Vec<i32>,
     param_modes: Vec<i32>,
@@ -94,17 +247,20 @@ struct Opcode {
 
 #[derive(Default)]
 /// Data representing the operation to execute
-struct Operation {
+pub struct Operation {
     /// Tuple of index and value
-    set_value: Option<(i32, i32)>,
+    pub set_value: Option<(i32, i32)>,
     /// Position in intcode to jump to
-    jump_to: Option<i32>,
-    wait: bool,
+    pub jump_to: Option<i32>,
+    pub wait: bool,
+    pub halt: bool,
 }
 
-fn get_or_error(intcode: &[i32], idx: i32) -> Result<i32, String> {
+fn get_or_error(intcode: &[i32], devices: &[devices::Mapping], idx: i32) -> Result<i32, String> {
     if let Ok(coerced_idx) = usize::try_from(idx) {
-        if let Some(&x) = intcode.get(coerced_idx) {
+        if let Some((device, offset)) = devices::find(devices, coerced_idx) {
+            device.read(offset)
+        } else if let Some(&x) = intcode.get(coerced_idx) {
             Ok(x)
         } else {
             Err(format!(
@@ -118,9 +274,16 @@ fn get_or_error(intcode: &[i32], idx: i32) -> Result<i32, String> {
     }
 }
 
-fn set_or_error(intcode: &mut Vec<i32>, idx: i32, val: i32) -> Result<(), String> {
+fn set_or_error(
+    intcode: &mut [i32],
+    devices: &[devices::Mapping],
+    idx: i32,
+    val: i32,
+) -> Result<(), String> {
     if let Ok(coerced_idx) = usize::try_from(idx) {
-        if let Some(x) = intcode.get_mut(coerced_idx) {
+        if let Some((device, offset)) = devices::find(devices, coerced_idx) {
+            device.write(offset, val)
+        } else if let Some(x) = intcode.get_mut(coerced_idx) {
             *x = val;
             Ok(())
         } else {
@@ -137,58 +300,100 @@ fn set_or_error(intcode: &mut Vec<i32>, idx: i32, val: i32) -> Result<(), String
 
 impl Opcode {
     pub fn execute(&self, computer: &mut IntcodeComputer) -> Result<(), String> {
-        let operation = match self.opcode {
-            1 => self.opcode_1(computer),
-            2 => self.opcode_2(computer),
-            3 => self.opcode_3(computer),
-            4 => self.opcode_4(computer),
-            5 => self.opcode_5(computer),
-            6 => self.opcode_6(computer),
-            7 => self.opcode_7(computer),
-            8 => self.opcode_8(computer),
-            _ => Err(format!("Cannot execute opcode: {}", self.opcode)),
-        }?;
+        self.traced_execute(computer).map(|_| ())
+    }
+
+    /// Like `execute`, but returns the address and value written, if any.
+    fn traced_execute(
+        &self,
+        computer: &mut IntcodeComputer,
+    ) -> Result<Option<(usize, i32)>, String> {
+        let handler = computer
+            .instruction_set
+            .handler(self.opcode)
+            .ok_or_else(|| format!("Cannot execute opcode: {}", self.opcode))?;
+        let operation = handler(self, computer)?;
+        if operation.halt {
+            computer.state = State::Terminated;
+            return Ok(None);
+        }
+        let mut written = None;
         if let Some((idx, val)) = operation.set_value {
-            set_or_error(&mut computer.intcode, idx, val)?
-        } else {
+            computer.set_memory(idx, val)?;
+            written = Some((idx as usize, val));
         }
         if let Some(position) = operation.jump_to {
-            computer.position = usize::try_from(position).unwrap();
+            computer.position = usize::try_from(position)
+                .map_err(|_| format!("Cannot jump to negative position {}", position))?;
         } else if !operation.wait {
             computer.position += self.operands.len() + 1;
         }
         if operation.wait {
             computer.state = State::WaitingForInput;
         }
-        Ok(())
+        Ok(written)
+    }
+
+    pub fn operands(&self) -> &[i32] {
+        &self.operands
+    }
+
+    pub fn param_modes(&self) -> &[i32] {
+        &self.param_modes
     }
 
-    fn read_params(&self, intcode: &[i32]) -> Result<Vec<i32>, String> {
+    /// Resolves every operand according to its parameter mode. Instructions
+    /// that write to one of their operands should use `read_inputs` and
+    /// `destination` instead, so that the address they write to is not read.
+    pub fn read_params(&self, computer: &IntcodeComputer) -> Result<Vec<i32>, String> {
+        self.read_inputs(computer, self.operands.len())
+    }
+
+    /// Resolves the first `count` operands according to their parameter modes.
+    pub fn read_inputs(
+        &self,
+        computer: &IntcodeComputer,
+        count: usize,
+    ) -> Result<Vec<i32>, String> {
         self.param_modes
             .iter()
             .zip(self.operands.iter())
+            .take(count)
             .map(|(param_mode, &num_at_position)| match param_mode {
-                0 => get_or_error(intcode, num_at_position),
+                0 => computer.get_memory(num_at_position),
                 1 => Ok(num_at_position),
                 _ => Err(String::from("unknown param mode")),
             })
             .collect()
     }
 
-    fn opcode_1(&self, computer: &IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
-        let sum = read_params[0] + read_params[1];
+    /// The address operand `n` (0-based) writes to. Its parameter mode is
+    /// checked, but the address itself is never read.
+    pub fn destination(&self, n: usize) -> Result<i32, String> {
+        match self.param_modes[n] {
+            0 | 1 => Ok(self.operands[n]),
+            _ => Err(String::from("unknown param mode")),
+        }
+    }
+
+    fn opcode_1(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
+        let read_params = self.read_inputs(computer, 2)?;
+        let sum = read_params[0]
+            .checked_add(read_params[1])
+            .ok_or_else(|| String::from("Integer overflow"))?;
         Ok(Operation {
-            set_value: Some((self.operands[2], sum)),
+            set_value: Some((self.destination(2)?, sum)),
             ..Default::default()
         })
     }
 
     fn opcode_2(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
-        let product = read_params[0] * read_params[1];
+        let read_params = self.read_inputs(computer, 2)?;
+        let product = read_params[0]
+            .checked_mul(read_params[1])
+            .ok_or_else(|| String::from("Integer overflow"))?;
         Ok(Operation {
-            set_value: Some((self.operands[2], product)),
+            set_value: Some((self.destination(2)?, product)),
             ..Default::default()
         })
     }
@@ -208,13 +413,13 @@ impl Opcode {
     }
 
     fn opcode_4(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
+        let read_params = self.read_params(computer)?;
         computer.produce_output(read_params[0])?;
         Ok(Default::default())
     }
 
     fn opcode_5(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
+        let read_params = self.read_params(computer)?;
         Ok(Operation {
             jump_to: if read_params[0] != 0 {
                 Some(read_params[1])
@@ -226,7 +431,7 @@ impl Opcode {
     }
 
     fn opcode_6(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
+        let read_params = self.read_params(computer)?;
         Ok(Operation {
             jump_to: if read_params[0] == 0 {
                 Some(read_params[1])
@@ -238,47 +443,70 @@ impl Opcode {
     }
 
     fn opcode_7(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
+        let read_params = self.read_inputs(computer, 2)?;
         let value_to_store = if read_params[0] < read_params[1] {
             1
         } else {
             0
         };
         Ok(Operation {
-            set_value: Some((self.operands[2], value_to_store)),
+            set_value: Some((self.destination(2)?, value_to_store)),
             ..Default::default()
         })
     }
 
     fn opcode_8(&self, computer: &mut IntcodeComputer) -> Result<Operation, String> {
-        let read_params = self.read_params(&computer.intcode)?;
+        let read_params = self.read_inputs(computer, 2)?;
         let value_to_store = if read_params[0] == read_params[1] {
             1
         } else {
             0
         };
         Ok(Operation {
-            set_value: Some((self.operands[2], value_to_store)),
+            set_value: Some((self.destination(2)?, value_to_store)),
             ..Default::default()
         })
     }
 
-    pub fn new(intcode: &[i32], position: usize) -> Self {
+    fn opcode_99(&self, _computer: &mut IntcodeComputer) -> Result<Operation, String> {
+        Ok(Operation {
+            halt: true,
+            ..Default::default()
+        })
+    }
+
+    pub fn new(
+        intcode: &[i32],
+        position: usize,
+        instruction_set: &InstructionSet,
+    ) -> Result<Self, String> {
         let num = intcode[position];
         let opcode = parse_opcode(num);
-        let num_operands = num_operands(opcode);
-        let slice = intcode[position + 1..].iter().copied().take(num_operands);
+        let num_operands = instruction_set
+            .num_operands(opcode)
+            .ok_or_else(|| format!("Unknown opcode: {}", opcode))?;
+        let operands: Vec<i32> = intcode[position + 1..]
+            .iter()
+            .copied()
+            .take(num_operands)
+            .collect();
+        if operands.len() < num_operands {
+            return Err(format!(
+                "Instruction at position {} is missing operands.",
+                position
+            ));
+        }
         let param_modes = parse_parameter_modes(num)
             .iter()
             .copied()
             .chain(iter::repeat(0))
             .take(num_operands)
             .collect();
-        Self {
-            operands: slice.collect(),
+        Ok(Self {
+            operands,
             opcode,
             param_modes,
-        }
+        })
     }
 }
 
@@ -286,21 +514,6 @@ fn parse_opcode(num: i32) -> i32 {
     num % 100
 }
 
-fn num_operands(opcode: i32) -> usize {
-    match opcode {
-        1 => 3,
-        2 => 3,
-        3 => 1,
-        4 => 1,
-        5 => 2,
-        6 => 2,
-        7 => 3,
-        8 => 3,
-        99 => 0,
-        _ => panic!("Unknown opcode: {}", opcode),
-    }
-}
-
 /// Takes a number representing parameter modes stuck to an opcode
 fn parse_parameter_modes(num: i32) -> Vec<i32> {
     let mut parameter_modes = Vec::new();
@@ -314,8 +527,8 @@ fn parse_parameter_modes(num: i32) -> Vec<i32> {
 }
 
 pub fn process_inputs(noun: i32, verb: i32, computer: &mut IntcodeComputer) -> Result<i32, String> {
-    computer.intcode[1] = noun;
-    computer.intcode[2] = verb;
+    computer.set_memory(1, noun)?;
+    computer.set_memory(2, verb)?;
 
     computer.run()?;
 
@@ -328,7 +541,7 @@ mod tests {
     #[test]
     fn opcode_1_test() {
         let mut computer = IntcodeComputer::new("1,9,10,3,2,3,11,0,99,30,40,50", Vec::new());
-        let operation = Opcode::new(&computer.intcode, 0);
+        let operation = Opcode::new(&computer.intcode, 0, &InstructionSet::default()).unwrap();
         operation.execute(&mut computer).unwrap();
         assert_eq!(
             vec![1, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
@@ -339,7 +552,7 @@ mod tests {
     #[test]
     fn opcode_2_test() {
         let mut computer = IntcodeComputer::new("1,9,10,70,2,3,11,0,99,30,40,50", Vec::new());
-        let operation = Opcode::new(&computer.intcode, 4);
+        let operation = Opcode::new(&computer.intcode, 4, &InstructionSet::default()).unwrap();
         operation.execute(&mut computer).unwrap();
         assert_eq!(
             vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
@@ -350,7 +563,7 @@ mod tests {
     #[test]
     fn opcode_constructor_works() {
         let intcode = parse_intcode("1002,4,3,4,33");
-        let operation = Opcode::new(&intcode, 0);
+        let operation = Opcode::new(&intcode, 0, &InstructionSet::default()).unwrap();
         assert_eq!(operation.operands, vec![4, 3, 4]);
         assert_eq!(operation.param_modes, vec![0, 1, 0]);
         assert_eq!(operation.opcode, 2);
@@ -441,4 +654,41 @@ mod tests {
         computer.run().unwrap();
         assert_eq!(computer.output, vec![1001]);
     }
+
+    #[test]
+    fn add_and_multiply_overflow_are_errors() {
+        let mut computer = IntcodeComputer::new("1101,2147483647,1,0,99", Vec::new());
+        assert_eq!(computer.run(), Err(String::from("Integer overflow")));
+        let mut computer = IntcodeComputer::new("1102,65536,65536,0,99", Vec::new());
+        assert_eq!(computer.run(), Err(String::from("Integer overflow")));
+        // Nothing is written when the result overflows.
+        assert_eq!(computer.memory()[0], 1102);
+    }
+
+    #[test]
+    fn negative_jump_targets_are_errors() {
+        let mut computer = IntcodeComputer::new("1105,1,-3,99", Vec::new());
+        assert_eq!(
+            computer.run(),
+            Err(String::from("Cannot jump to negative position -3"))
+        );
+        // Not taking the jump is fine.
+        let mut computer = IntcodeComputer::new("1106,1,-3,99", Vec::new());
+        assert_eq!(computer.run(), Ok(()));
+    }
+
+    #[test]
+    fn decoding_bad_instructions_fails() {
+        let set = InstructionSet::default();
+        assert_eq!(
+            Opcode::new(&[42, 0, 0], 0, &set).err(),
+            Some(String::from("Unknown opcode: 42"))
+        );
+        assert_eq!(
+            Opcode::new(&[99, 1, 2, 1], 1, &set).err(),
+            Some(String::from(
+                "Instruction at position 1 is missing operands."
+            ))
+        );
+    }
 }
@@ -0,0 +1,379 @@
+//! A deliberately simple Intcode interpreter used as a semantic reference for
+//! `IntcodeComputer`. It favours obviousness over speed: every instruction is
+//! decoded from scratch with plain arithmetic and executed inline.
+
+use crate::{parse_intcode, State};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+pub struct ReferenceComputer {
+    pub memory: Vec<i32>,
+    pub position: usize,
+    pub input: VecDeque<i32>,
+    pub output: Vec<i32>,
+    pub state: State,
+    /// Cells past the end of memory that can be written but not read, like a
+    /// write-only device. Instructions are never fetched from them.
+    pub write_only: Vec<i32>,
+}
+
+impl ReferenceComputer {
+    pub fn new(raw_intcode: &str, input: Vec<i32>) -> Self {
+        Self {
+            memory: parse_intcode(raw_intcode),
+            position: 0,
+            input: input.into_iter().collect(),
+            output: Vec::new(),
+            state: State::Initialized,
+            write_only: Vec::new(),
+        }
+    }
+
+    fn load(&self, address: i32) -> Result<i32, String> {
+        usize::try_from(address)
+            .ok()
+            .and_then(|address| self.memory.get(address).copied())
+            .ok_or_else(|| format!("Cannot read address {}", address))
+    }
+
+    fn store(&mut self, address: i32, value: i32) -> Result<(), String> {
+        let len = self.memory.len();
+        match usize::try_from(address).ok().and_then(|address| {
+            if address < len {
+                self.memory.get_mut(address)
+            } else {
+                self.write_only.get_mut(address - len)
+            }
+        }) {
+            Some(cell) => {
+                *cell = value;
+                Ok(())
+            }
+            None => Err(format!("Cannot write address {}", address)),
+        }
+    }
+
+    /// Returns the raw operand at offset `n` (1-based) from the current position.
+    fn operand(&self, n: usize) -> Result<i32, String> {
+        self.memory
+            .get(self.position + n)
+            .copied()
+            .ok_or_else(|| String::from("Instruction is missing operands."))
+    }
+
+    /// Returns the value of parameter `n` (1-based), honouring its mode.
+    fn param(&self, n: usize) -> Result<i32, String> {
+        let operand = self.operand(n)?;
+        match self.mode(n) {
+            0 => self.load(operand),
+            1 => Ok(operand),
+            mode => Err(format!("Unknown parameter mode {}", mode)),
+        }
+    }
+
+    fn mode(&self, n: usize) -> i32 {
+        let instruction = self.memory[self.position];
+        if instruction < 0 {
+            0
+        } else {
+            instruction / 10_i32.pow(n as u32 + 1) % 10
+        }
+    }
+
+    /// Returns the address parameter `n` (1-based) writes to. The address is
+    /// never read, but its mode still has to be valid.
+    fn destination(&self, n: usize) -> Result<i32, String> {
+        let operand = self.operand(n)?;
+        match self.mode(n) {
+            0 | 1 => Ok(operand),
+            mode => Err(format!("Unknown parameter mode {}", mode)),
+        }
+    }
+
+    fn arithmetic(&mut self, f: fn(i32, i32) -> Option<i32>) -> Result<(), String> {
+        let value = f(self.param(1)?, self.param(2)?).ok_or("Integer overflow")?;
+        self.store(self.destination(3)?, value)?;
+        self.position += 4;
+        Ok(())
+    }
+
+    fn jump(&mut self, condition: fn(i32) -> bool) -> Result<(), String> {
+        let (value, target) = (self.param(1)?, self.param(2)?);
+        if condition(value) {
+            self.position = usize::try_from(target)
+                .map_err(|_| format!("Cannot jump to negative position {}", target))?;
+        } else {
+            self.position += 3;
+        }
+        Ok(())
+    }
+
+    fn compare(&mut self, f: fn(i32, i32) -> bool) -> Result<(), String> {
+        let value = if f(self.param(1)?, self.param(2)?) {
+            1
+        } else {
+            0
+        };
+        self.store(self.destination(3)?, value)?;
+        self.position += 4;
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), String> {
+        self.state = State::Running;
+        let instruction = *self
+            .memory
+            .get(self.position)
+            .ok_or_else(|| String::from("EOF error"))?;
+        match instruction % 100 {
+            1 => self.arithmetic(i32::checked_add),
+            2 => self.arithmetic(i32::checked_mul),
+            3 => {
+                self.operand(1)?;
+                match self.input.pop_front() {
+                    Some(value) => {
+                        self.store(self.operand(1)?, value)?;
+                        self.position += 2;
+                    }
+                    None => self.state = State::WaitingForInput,
+                }
+                Ok(())
+            }
+            4 => {
+                self.output.push(self.param(1)?);
+                self.position += 2;
+                Ok(())
+            }
+            5 => self.jump(|x| x != 0),
+            6 => self.jump(|x| x == 0),
+            7 => self.compare(|a, b| a < b),
+            8 => self.compare(|a, b| a == b),
+            99 => {
+                self.state = State::Terminated;
+                Ok(())
+            }
+            opcode => Err(format!("Unknown opcode: {}", opcode)),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        self.step()?;
+        while self.state == State::Running {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::devices::tests::WriteOnly;
+    use crate::{read_file, IntcodeComputer};
+    use alloc::sync::Arc;
+    use std::sync::Mutex;
+
+    /// Steps both interpreters in lockstep and asserts that they agree on
+    /// memory, position, outputs and state after every instruction.
+    fn assert_equivalent(raw_code: &str, input: Vec<i32>, max_steps: usize) {
+        assert_equivalent_with_write_only(raw_code, input, max_steps, 0);
+    }
+
+    /// Like `assert_equivalent`, but with `extra` write-only cells past the end
+    /// of memory: a device in `IntcodeComputer`, and `write_only` in the reference.
+    fn assert_equivalent_with_write_only(
+        raw_code: &str,
+        input: Vec<i32>,
+        max_steps: usize,
+        extra: usize,
+    ) {
+        let mut computer = IntcodeComputer::new(raw_code, input.clone());
+        let mut reference = ReferenceComputer::new(raw_code, input);
+        let len = reference.memory.len();
+        let device = Arc::new(WriteOnly(Mutex::new(vec![0; extra])));
+        if extra > 0 {
+            computer
+                .map_device(len..len + extra, device.clone())
+                .unwrap();
+            reference.write_only = vec![0; extra];
+        }
+
+        for step in 0..max_steps {
+            let result = computer.step();
+            let expected = reference.step();
+            assert_eq!(
+                result.is_err(),
+                expected.is_err(),
+                "step {} of {}: got {:?}, reference got {:?}",
+                step,
+                raw_code,
+                result,
+                expected
+            );
+            if expected.is_err() {
+                return;
+            }
+            assert_eq!(
+                computer.memory(),
+                &reference.memory[..],
+                "step {} of {}",
+                step,
+                raw_code
+            );
+            assert_eq!(
+                *device.0.lock().unwrap(),
+                reference.write_only,
+                "step {} of {}",
+                step,
+                raw_code
+            );
+            assert_eq!(
+                computer.position(),
+                reference.position,
+                "step {} of {}",
+                step,
+                raw_code
+            );
+            assert_eq!(
+                computer.output, reference.output,
+                "step {} of {}",
+                step, raw_code
+            );
+            assert_eq!(
+                computer.state, reference.state,
+                "step {} of {}",
+                step, raw_code
+            );
+            if reference.state != State::Running {
+                return;
+            }
+        }
+    }
+
+    /// xorshift32, so that random programs are reproducible without extra dependencies.
+    pub(crate) struct Rng(pub(crate) u32);
+
+    impl Rng {
+        pub(crate) fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        pub(crate) fn below(&mut self, n: u32) -> i32 {
+            (self.next() % n) as i32
+        }
+    }
+
+    pub(crate) fn random_program(rng: &mut Rng) -> String {
+        let len = 8 + rng.below(24);
+        (0..len)
+            .map(|_| match rng.below(10) {
+                0..=5 => {
+                    let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 99][rng.below(9) as usize];
+                    let modes = rng.below(2) * 100 + rng.below(2) * 1000 + rng.below(3) * 10000;
+                    opcode + modes
+                }
+                6..=8 => rng.below(len as u32 + 2),
+                _ => rng.below(2000) - 1000,
+            })
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn day5_programs_match_reference() {
+        let programs = [
+            "3,0,4,0,99",
+            "1002,4,3,4,33",
+            "3,9,8,9,10,9,4,9,99,-1,8",
+            "3,9,7,9,10,9,4,9,99,-1,8",
+            "3,3,1108,-1,8,3,4,3,99",
+            "3,3,1107,-1,8,3,4,3,99",
+            "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+            "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        ];
+        for program in programs.iter() {
+            for &input in [-1, 0, 1, 7, 8, 9, 1000].iter() {
+                assert_equivalent(program, vec![input], 10_000);
+            }
+        }
+
+        let raw_code = read_file("../day5/input");
+        assert_equivalent(&raw_code, vec![1], 100_000);
+        assert_equivalent(&raw_code, vec![5], 100_000);
+    }
+
+    #[test]
+    fn day7_programs_match_reference() {
+        let programs = [
+            "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0",
+            "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+            "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0",
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+        ];
+        let raw_input = read_file("../day7/input");
+        for program in programs.iter().copied().chain(Some(raw_input.as_str())) {
+            for phase_setting in 0..10 {
+                assert_equivalent(program, vec![phase_setting, 0], 10_000);
+                assert_equivalent(program, vec![phase_setting, 12_345], 10_000);
+            }
+        }
+    }
+
+    #[test]
+    fn random_programs_match_reference() {
+        let mut rng = Rng(0x2019_0c0d);
+        for _ in 0..5_000 {
+            let program = random_program(&mut rng);
+            let input = (0..rng.below(4)).map(|_| rng.below(21) - 10).collect();
+            assert_equivalent(&program, input, 500);
+        }
+    }
+
+    #[test]
+    fn random_programs_match_reference_with_write_only_cells() {
+        let mut rng = Rng(0x0033_d35c);
+        for _ in 0..5_000 {
+            let program = random_program(&mut rng);
+            let input = (0..rng.below(4)).map(|_| rng.below(21) - 10).collect();
+            assert_equivalent_with_write_only(&program, input, 500, 2);
+        }
+    }
+
+    #[test]
+    fn overflow_negative_jumps_and_bad_instructions_are_errors() {
+        let programs = [
+            "1101,2147483647,1,0,99",
+            "1102,65536,65536,0,99",
+            "1105,1,-3,99",
+            "42,0,0",
+            "1,0,0",
+        ];
+        for program in programs.iter() {
+            let mut reference = ReferenceComputer::new(program, Vec::new());
+            assert!(reference.run().is_err(), "{}", program);
+            assert_equivalent(program, Vec::new(), 10);
+        }
+    }
+
+    #[test]
+    fn only_input_operands_are_read() {
+        // The destination is only written.
+        let mut reference = ReferenceComputer::new("1101,2,3,5,99,-7", Vec::new());
+        reference.run().unwrap();
+        assert_eq!(reference.memory[5], 5);
+        // Its mode still has to be valid.
+        let mut reference = ReferenceComputer::new("21101,2,3,5,99,-7", Vec::new());
+        assert_eq!(
+            reference.run(),
+            Err(String::from("Unknown parameter mode 2"))
+        );
+    }
+}
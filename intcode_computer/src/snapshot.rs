@@ -0,0 +1,272 @@
+//! Versioned, serde-friendly snapshots of programs and paused machines.
+//!
+//! Every snapshot starts with its `version`, which is read before anything
+//! else, so that each version can have a body of its own shape and snapshots
+//! from newer versions are rejected before their body is looked at. In
+//! self-describing formats `version` must be the first field, which is where
+//! `Serialize` puts it.
+//!
+//! A version 1 snapshot only needs `version` and `program`; every other field
+//! defaults to that of a freshly loaded machine, so plain programs can be
+//! stored as snapshots too.
+
+use crate::{Engine, IntcodeComputer, State};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The snapshot format version written by this crate.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub program: Vec<i32>,
+    pub position: usize,
+    /// Pending input, in the order it will be consumed.
+    pub input: Vec<i32>,
+    pub output: Vec<i32>,
+    pub state: State,
+}
+
+/// Everything in a version 1 snapshot after its version.
+#[derive(Deserialize)]
+struct BodyV1 {
+    program: Vec<i32>,
+    #[serde(default)]
+    position: usize,
+    #[serde(default)]
+    input: Vec<i32>,
+    #[serde(default)]
+    output: Vec<i32>,
+    #[serde(default = "initialized")]
+    state: State,
+}
+
+fn initialized() -> State {
+    State::Initialized
+}
+
+impl BodyV1 {
+    fn into_snapshot(self) -> Snapshot {
+        Snapshot {
+            version: 1,
+            program: self.program,
+            position: self.position,
+            input: self.input,
+            output: self.output,
+            state: self.state,
+        }
+    }
+}
+
+/// Checks that this crate can load snapshots of format `version`.
+fn check_version(version: u32) -> Result<(), String> {
+    match version {
+        SNAPSHOT_VERSION => Ok(()),
+        version if version > SNAPSHOT_VERSION => Err(format!(
+            "Snapshot format version {} is newer than the supported version {}. Upgrade intcode_computer to load it.",
+            version, SNAPSHOT_VERSION
+        )),
+        version => Err(format!("Unknown snapshot format version {}.", version)),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Key {
+    Version,
+    #[serde(other)]
+    Other,
+}
+
+struct SnapshotVisitor;
+
+impl<'de> Visitor<'de> for SnapshotVisitor {
+    type Value = Snapshot;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an Intcode snapshot starting with its version")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Snapshot, A::Error> {
+        match map.next_key()? {
+            Some(Key::Version) => {}
+            _ => return Err(de::Error::custom("A snapshot must start with its version.")),
+        }
+        check_version(map.next_value()?).map_err(de::Error::custom)?;
+        BodyV1::deserialize(MapAccessDeserializer::new(map)).map(BodyV1::into_snapshot)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Snapshot, A::Error> {
+        let version = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::custom("A snapshot must start with its version."))?;
+        check_version(version).map_err(de::Error::custom)?;
+        BodyV1::deserialize(SeqAccessDeserializer::new(seq)).map(BodyV1::into_snapshot)
+    }
+}
+
+impl<'de> Deserialize<'de> for Snapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        const FIELDS: &[&str] = &["version", "program", "position", "input", "output", "state"];
+        deserializer.deserialize_struct("Snapshot", FIELDS, SnapshotVisitor)
+    }
+}
+
+impl Snapshot {
+    pub fn from_program(program: Vec<i32>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            program,
+            position: 0,
+            input: Vec::new(),
+            output: Vec::new(),
+            state: State::Initialized,
+        }
+    }
+}
+
+impl IntcodeComputer {
+    /// Saves the program, position, I/O and state. The engine and any
+    /// recorded history are not saved. Computers with a custom instruction
+    /// set or memory-mapped devices cannot be saved, since neither could be
+    /// restored.
+    pub fn to_snapshot(&self) -> Result<Snapshot, String> {
+        if !self.instruction_set.is_default() {
+            return Err(String::from(
+                "Computers with a custom instruction set cannot be saved.",
+            ));
+        }
+        if !self.devices.is_empty() {
+            return Err(String::from(
+                "Computers with memory-mapped devices cannot be saved.",
+            ));
+        }
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            program: self.intcode.clone(),
+            position: self.position,
+            input: self.input.iter().rev().copied().collect(),
+            output: self.output.clone(),
+            state: self.state.clone(),
+        })
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, String> {
+        check_version(snapshot.version)?;
+        Ok(Self {
+            intcode: snapshot.program,
+            position: snapshot.position,
+            input: snapshot.input.into_iter().rev().collect(),
+            output: snapshot.output,
+            state: snapshot.state,
+            instruction_set: Default::default(),
+            engine: Engine::Interpreter,
+            blocks: Default::default(),
+            devices: Vec::new(),
+            history: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paused_computer() -> IntcodeComputer {
+        let mut computer = IntcodeComputer::new("3,0,3,1,4,0,4,1,99", vec![]);
+        computer.run().unwrap();
+        computer.feed_input(7).unwrap();
+        computer
+    }
+
+    #[test]
+    fn json_round_trip_resumes_paused_computer() {
+        let json = serde_json::to_string(&paused_computer().to_snapshot().unwrap()).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        let mut computer = IntcodeComputer::from_snapshot(snapshot).unwrap();
+        assert_eq!(computer.state, State::WaitingForInput);
+        computer.feed_input(8).unwrap();
+        assert_eq!(computer.output, vec![7, 8]);
+        assert_eq!(computer.state, State::Terminated);
+    }
+
+    #[test]
+    fn ron_round_trip_preserves_input_order() {
+        let computer = IntcodeComputer::new("3,0,3,1,4,0,4,1,99", vec![1, 2]);
+        let ron = ron::to_string(&computer.to_snapshot().unwrap()).unwrap();
+        let snapshot: Snapshot = ron::from_str(&ron).unwrap();
+        assert_eq!(snapshot.input, vec![1, 2]);
+        let mut computer = IntcodeComputer::from_snapshot(snapshot).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![1, 2]);
+    }
+
+    #[test]
+    fn program_only_snapshot_loads() {
+        let snapshot: Snapshot =
+            serde_json::from_str(r#"{"version": 1, "program": [3, 0, 4, 0, 99]}"#).unwrap();
+        assert_eq!(snapshot, Snapshot::from_program(vec![3, 0, 4, 0, 99]));
+        let mut computer = IntcodeComputer::from_snapshot(snapshot).unwrap();
+        computer.feed_input(5).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![5]);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        // A body that looks nothing like version 1.
+        let json = r#"{"version": 2, "memory": {"cells": [99]}, "cpu": {"pc": 0}}"#;
+        let error = serde_json::from_str::<Snapshot>(json).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("newer than the supported version 1"));
+        let ron = "(version: 2, memory: (cells: [99]))";
+        assert!(ron::from_str::<Snapshot>(ron)
+            .unwrap_err()
+            .to_string()
+            .contains("newer than the supported version 1"));
+
+        let mut snapshot = Snapshot::from_program(vec![99]);
+        snapshot.version = 2;
+        let error = IntcodeComputer::from_snapshot(snapshot).err().unwrap();
+        assert!(error.contains("newer than the supported version 1"));
+    }
+
+    #[test]
+    fn version_comes_first() {
+        let error =
+            serde_json::from_str::<Snapshot>(r#"{"program": [99], "version": 1}"#).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("A snapshot must start with its version."));
+        let error =
+            serde_json::from_str::<Snapshot>(r#"{"version": 0, "program": [99]}"#).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Unknown snapshot format version 0."));
+    }
+
+    #[test]
+    fn custom_machines_cannot_be_saved() {
+        let mut instruction_set = crate::InstructionSet::default();
+        instruction_set.register(10, 0, |_, _| Ok(Default::default()));
+        let computer = IntcodeComputer::with_instruction_set(
+            "10,99",
+            vec![],
+            alloc::sync::Arc::new(instruction_set),
+        );
+        assert!(computer.to_snapshot().is_err());
+
+        let mut computer = IntcodeComputer::new("99", vec![]);
+        let device = crate::devices::tests::WriteOnly(std::sync::Mutex::new(vec![0]));
+        computer
+            .map_device(10..11, alloc::sync::Arc::new(device))
+            .unwrap();
+        assert!(computer.to_snapshot().is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! A basic-block execution engine.
+//!
+//! Straight-line runs of instructions are decoded once into blocks of
+//! pre-resolved operations, which are then executed without any further
+//! decoding. A block is thrown away as soon as a cell inside it is written.
+//! Anything a block cannot execute exactly like the interpreter, such as an
+//! arithmetic overflow or an unknown opcode, is handed to `IntcodeComputer::step`.
+//! Computers with custom opcodes or memory-mapped devices are always interpreted.
+
+use crate::{IntcodeComputer, State};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Immediate(i32),
+    Position(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add(Operand, Operand, usize),
+    Multiply(Operand, Operand, usize),
+    Input(usize),
+    Output(Operand),
+    JumpIfTrue(Operand, Operand),
+    JumpIfFalse(Operand, Operand),
+    LessThan(Operand, Operand, usize),
+    Equals(Operand, Operand, usize),
+    Halt,
+    /// Leave this instruction to the interpreter.
+    Interpret,
+}
+
+struct Block {
+    /// Position of the first cell after the block.
+    end: usize,
+    /// Each operation along with the position of its instruction.
+    ops: Vec<(usize, Op)>,
+}
+
+/// The decoded blocks of a computer, keyed by their starting position.
+#[derive(Clone, Default)]
+pub(crate) struct BlockCache {
+    blocks: Vec<Option<Arc<Block>>>,
+    /// Whether each memory cell belongs to at least one cached block.
+    covered: Vec<bool>,
+}
+
+impl BlockCache {
+    /// Drops every block containing `address`. Returns whether any block was dropped.
+    pub(crate) fn invalidate(&mut self, address: usize) -> bool {
+        if !self.covered.get(address).copied().unwrap_or(false) {
+            return false;
+        }
+        for (start, slot) in self.blocks.iter_mut().enumerate() {
+            if let Some(block) = slot {
+                if start <= address && address < block.end {
+                    *slot = None;
+                }
+            }
+        }
+        for covered in self.covered.iter_mut() {
+            *covered = false;
+        }
+        for (start, block) in self.blocks.iter().enumerate() {
+            if let Some(block) = block {
+                for covered in &mut self.covered[start..block.end] {
+                    *covered = true;
+                }
+            }
+        }
+        true
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+        self.covered.clear();
+    }
+
+    fn get_or_decode(&mut self, memory: &[i32], start: usize) -> Arc<Block> {
+        if self.blocks.len() != memory.len() {
+            self.blocks = vec![None; memory.len()];
+            self.covered = vec![false; memory.len()];
+        }
+        if let Some(block) = &self.blocks[start] {
+            return Arc::clone(block);
+        }
+        let block = Arc::new(decode_block(memory, start));
+        for covered in &mut self.covered[start..block.end] {
+            *covered = true;
+        }
+        self.blocks[start] = Some(Arc::clone(&block));
+        block
+    }
+}
+
+fn operand(memory: &[i32], position: usize, n: usize) -> Option<Operand> {
+    let raw = memory[position + n];
+    match memory[position] / 10_i32.pow(n as u32 + 1) % 10 {
+        0 => usize::try_from(raw)
+            .ok()
+            .filter(|&address| address < memory.len())
+            .map(Operand::Position),
+        1 => Some(Operand::Immediate(raw)),
+        _ => None,
+    }
+}
+
+/// Resolves a write target the way the interpreter validates it.
+fn target(memory: &[i32], position: usize, n: usize) -> Option<usize> {
+    operand(memory, position, n)?;
+    usize::try_from(memory[position + n])
+        .ok()
+        .filter(|&address| address < memory.len())
+}
+
+fn decode_op(memory: &[i32], position: usize) -> Option<(Op, usize)> {
+    let num = memory[position];
+    if num < 0 {
+        return None;
+    }
+    let length = match num % 100 {
+        1 | 2 | 7 | 8 => 4,
+        3 | 4 => 2,
+        5 | 6 => 3,
+        99 => 1,
+        _ => return None,
+    };
+    if position + length > memory.len() {
+        return None;
+    }
+    let p = |n| operand(memory, position, n);
+    let op = match num % 100 {
+        1 => Op::Add(p(1)?, p(2)?, target(memory, position, 3)?),
+        2 => Op::Multiply(p(1)?, p(2)?, target(memory, position, 3)?),
+        3 => Op::Input(
+            usize::try_from(memory[position + 1])
+                .ok()
+                .filter(|&address| address < memory.len())?,
+        ),
+        4 => Op::Output(p(1)?),
+        5 => Op::JumpIfTrue(p(1)?, p(2)?),
+        6 => Op::JumpIfFalse(p(1)?, p(2)?),
+        7 => Op::LessThan(p(1)?, p(2)?, target(memory, position, 3)?),
+        8 => Op::Equals(p(1)?, p(2)?, target(memory, position, 3)?),
+        _ => Op::Halt,
+    };
+    Some((op, length))
+}
+
+/// Decodes instructions from `start` up to and including the first jump, halt
+/// or instruction that has to be interpreted.
+fn decode_block(memory: &[i32], start: usize) -> Block {
+    let mut ops = Vec::new();
+    let mut position = start;
+    loop {
+        match decode_op(memory, position) {
+            Some((op, length)) => {
+                ops.push((position, op));
+                position += length;
+                match op {
+                    Op::JumpIfTrue(..) | Op::JumpIfFalse(..) | Op::Halt => break,
+                    _ if position >= memory.len() => break,
+                    _ => {}
+                }
+            }
+            None => {
+                ops.push((position, Op::Interpret));
+                position += 1;
+                break;
+            }
+        }
+    }
+    Block { end: position, ops }
+}
+
+fn value(memory: &[i32], operand: Operand) -> i32 {
+    match operand {
+        Operand::Immediate(value) => value,
+        Operand::Position(address) => memory[address],
+    }
+}
+
+/// Where to go after leaving a block.
+enum Next {
+    /// Resume at this position.
+    Goto(usize),
+    /// Let the interpreter execute the instruction at this position.
+    Interpret(usize),
+    Stop,
+}
+
+/// Executes `op`, returning `None` if the block should carry on.
+fn execute(computer: &mut IntcodeComputer, position: usize, op: Op) -> Option<Next> {
+    let memory = &computer.intcode;
+    let (address, result) = match op {
+        Op::Add(a, b, t) => (t, value(memory, a).checked_add(value(memory, b))),
+        Op::Multiply(a, b, t) => (t, value(memory, a).checked_mul(value(memory, b))),
+        Op::LessThan(a, b, t) => (t, Some((value(memory, a) < value(memory, b)) as i32)),
+        Op::Equals(a, b, t) => (t, Some((value(memory, a) == value(memory, b)) as i32)),
+        Op::Input(t) => match computer.consume_input() {
+            Some(input) => (t, Some(input)),
+            None => {
+                computer.position = position;
+                computer.state = State::WaitingForInput;
+                return Some(Next::Stop);
+            }
+        },
+        Op::Output(a) => {
+            let output = value(memory, a);
+            computer.output.push(output);
+            return None;
+        }
+        Op::JumpIfTrue(a, b) | Op::JumpIfFalse(a, b) => {
+            let condition = value(memory, a) != 0;
+            let jump = if let Op::JumpIfTrue(..) = op {
+                condition
+            } else {
+                !condition
+            };
+            return Some(if !jump {
+                Next::Goto(position + 3)
+            } else {
+                match usize::try_from(value(memory, b)) {
+                    Ok(target) => Next::Goto(target),
+                    Err(_) => Next::Interpret(position),
+                }
+            });
+        }
+        Op::Halt => {
+            computer.position = position;
+            computer.state = State::Terminated;
+            return Some(Next::Stop);
+        }
+        Op::Interpret => return Some(Next::Interpret(position)),
+    };
+    match result {
+        Some(value) => {
+            computer.intcode[address] = value;
+            if computer.blocks.invalidate(address) {
+                let length = if let Op::Input(_) = op { 2 } else { 4 };
+                Some(Next::Goto(position + length))
+            } else {
+                None
+            }
+        }
+        None => Some(Next::Interpret(position)),
+    }
+}
+
+/// Runs `computer` until it halts, waits for input or fails.
+pub(crate) fn run(computer: &mut IntcodeComputer) -> Result<(), String> {
+    computer.state = State::Running;
+    loop {
+        if computer.position >= computer.intcode.len() {
+            return Err(String::from("EOF error"));
+        }
+        let block = computer
+            .blocks
+            .get_or_decode(&computer.intcode, computer.position);
+        let next = block
+            .ops
+            .iter()
+            .find_map(|&(position, op)| execute(computer, position, op))
+            .unwrap_or(Next::Goto(block.end));
+        match next {
+            Next::Goto(position) => computer.position = position,
+            Next::Interpret(position) => {
+                computer.position = position;
+                computer.step()?;
+                if computer.state != State::Running {
+                    return Ok(());
+                }
+            }
+            Next::Stop => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reference::tests::{random_program, Rng};
+    use crate::{read_file, Engine, IntcodeComputer, State};
+
+    fn assert_same_as_interpreter(raw_code: &str, inputs: &[i32]) {
+        let mut interpreted = IntcodeComputer::new(raw_code, Vec::new());
+        let mut threaded = IntcodeComputer::with_engine(raw_code, Vec::new(), Engine::Threaded);
+        let mut inputs = inputs.iter();
+        loop {
+            let interpreted_result = interpreted.run();
+            assert_eq!(threaded.run(), interpreted_result, "{}", raw_code);
+            assert_eq!(threaded.memory(), interpreted.memory(), "{}", raw_code);
+            assert_eq!(threaded.position(), interpreted.position(), "{}", raw_code);
+            assert_eq!(threaded.output, interpreted.output, "{}", raw_code);
+            assert_eq!(threaded.state, interpreted.state, "{}", raw_code);
+            if interpreted_result.is_err() || interpreted.state != State::WaitingForInput {
+                return;
+            }
+            match inputs.next() {
+                Some(&input) => {
+                    let _ = interpreted.feed_input(input);
+                    let _ = threaded.feed_input(input);
+                }
+                None => return,
+            }
+        }
+    }
+
+    #[test]
+    fn example_programs_match_interpreter() {
+        let programs = [
+            "1,9,10,3,2,3,11,0,99,30,40,50",
+            "1002,4,3,4,33",
+            "3,9,8,9,10,9,4,9,99,-1,8",
+            "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            // Overwrites the add at 4 with a halt before reaching it.
+            "1101,0,99,4,1,0,0,0,104,1,99",
+            // Overwrites the next instruction of its own block.
+            "1101,0,104,5,1,0,0,0,99",
+        ];
+        for program in programs.iter() {
+            for &input in [0, 7, 8, 9].iter() {
+                assert_same_as_interpreter(program, &[input, 3, 4, 5]);
+            }
+        }
+    }
+
+    #[test]
+    fn puzzle_inputs_match_interpreter() {
+        let day5 = read_file("../day5/input");
+        assert_same_as_interpreter(&day5, &[1]);
+        assert_same_as_interpreter(&day5, &[5]);
+        let day7 = read_file("../day7/input");
+        for phase_setting in 0..10 {
+            assert_same_as_interpreter(&day7, &[phase_setting, 0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn random_programs_match_interpreter() {
+        let mut rng = Rng(0x7e57_b10c);
+        for _ in 0..5_000 {
+            let program = random_program(&mut rng);
+            let inputs: Vec<i32> = (0..4).map(|_| rng.below(21) - 10).collect();
+            // Only compare programs that stop, since `run` has no step limit.
+            let mut bounded = IntcodeComputer::new(&program, inputs.clone());
+            let stops =
+                (0..500).any(|_| bounded.step().is_err() || bounded.state != State::Running);
+            if stops {
+                assert_same_as_interpreter(&program, &inputs);
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! JavaScript bindings, enabled by the `wasm-bindgen` feature.
+
+use crate::{IntcodeComputer, State};
+use alloc::string::String;
+use alloc::vec::Vec;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct Computer {
+    computer: IntcodeComputer,
+}
+
+#[wasm_bindgen]
+impl Computer {
+    /// Loads a comma separated program, ignoring whitespace.
+    #[wasm_bindgen(constructor)]
+    pub fn load(raw_intcode: &str) -> Result<Computer, JsValue> {
+        let raw_intcode: String = raw_intcode.chars().filter(|c| !c.is_whitespace()).collect();
+        if raw_intcode
+            .split(',')
+            .any(|item| item.parse::<i32>().is_err())
+        {
+            return Err(JsValue::from_str(
+                "Program is not a comma separated list of integers.",
+            ));
+        }
+        Ok(Self {
+            computer: IntcodeComputer::new(&raw_intcode, Vec::new()),
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), JsValue> {
+        self.computer.run().map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn feed(&mut self, input: i32) -> Result<(), JsValue> {
+        self.computer
+            .feed_input(input)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn outputs(&self) -> Vec<i32> {
+        self.computer.output.clone()
+    }
+
+    /// One of "Initialized", "Running", "WaitingForInput" or "Terminated".
+    pub fn state(&self) -> String {
+        match self.computer.state {
+            State::Initialized => String::from("Initialized"),
+            State::Running => String::from("Running"),
+            State::WaitingForInput => String::from("WaitingForInput"),
+            State::Terminated => String::from("Terminated"),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Compiles the example programs and puzzle inputs ahead of time into a crate
+//! of their own, then builds and runs it to check the compiled code against
+//! the interpreter. The checks themselves are in `aot/checks.rs`.
+
+use intcode_computer::{aot, parse_intcode, read_file};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const EXAMPLES: &[&str] = &[
+    "1,9,10,3,2,3,11,0,99,30,40,50",
+    "1002,4,3,4,33",
+    "3,0,4,0,99",
+    "3,9,8,9,10,9,4,9,99,-1,8",
+    "3,9,7,9,10,9,4,9,99,-1,8",
+    "3,3,1108,-1,8,3,4,3,99",
+    "3,3,1107,-1,8,3,4,3,99",
+    "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+    "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+    "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+    "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0",
+    "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+    "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0",
+    "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+    "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+    "1101,0,99,4,1,0,0,0,104,1,99",
+];
+
+/// Compiled after the examples, in this order.
+const INPUT_FILES: &[&str] = &["../day2/input", "../day5/input", "../day7/input"];
+
+/// The source of the checking crate: every program compiled into a function,
+/// a `FIXTURES` table of them, and the checks.
+fn main_source(manifest_dir: &str) -> String {
+    let mut programs: Vec<String> = EXAMPLES.iter().map(|&s| String::from(s)).collect();
+    for path in INPUT_FILES {
+        programs.push(
+            read_file(path)
+                .chars()
+                .filter(|&c| c.is_ascii_digit() || c == ',' || c == '-')
+                .collect(),
+        );
+    }
+    let mut source = String::new();
+    let mut entries = Vec::new();
+    for (i, program) in programs.iter().enumerate() {
+        let name = format!("fixture_{}", i);
+        source.push_str(&aot::compile(&parse_intcode(program), &name));
+        entries.push(format!("    ({:?}, {}),\n", program, name));
+    }
+    source.push_str("const FIXTURES: &[(&str, intcode_computer::aot::Compiled)] = &[\n");
+    source.push_str(&entries.concat());
+    source.push_str("];\n\n");
+    let checks = Path::new(manifest_dir).join("tests/aot/checks.rs");
+    source.push_str(&format!("include!({:?});\n", checks));
+    source
+}
+
+#[test]
+fn compiled_fixtures_match_interpreter() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("aot_fixtures");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let manifest = format!(
+        "[package]\n\
+         name = \"aot_fixtures\"\n\
+         version = \"0.0.0\"\n\
+         edition = \"2018\"\n\n\
+         [dependencies]\n\
+         intcode_computer = {{ path = {:?} }}\n\n\
+         [workspace]\n",
+        manifest_dir
+    );
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    fs::write(dir.join("src/main.rs"), main_source(manifest_dir)).unwrap();
+
+    let output = Command::new(env!("CARGO"))
+        .args(["run", "--offline", "--quiet", "--manifest-path"])
+        .arg(dir.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", dir.join("target"))
+        .current_dir(manifest_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
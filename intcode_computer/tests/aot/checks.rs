@@ -0,0 +1,108 @@
+// Checks the compiled fixtures against the interpreter. `tests/aot.rs` builds
+// this together with the generated `FIXTURES` into a crate of its own, and
+// runs it from the `intcode_computer` directory.
+
+use intcode_computer::aot::{Compiled, Exit, Io};
+use intcode_computer::{read_file, IntcodeComputer, State};
+use std::collections::VecDeque;
+
+fn assert_same_as_interpreter(computer: &IntcodeComputer, compiled: Compiled) {
+    let mut interpreted = computer.clone();
+    let interpreted_result = interpreted.run();
+    let mut compiled_computer = computer.clone();
+    let compiled_result = compiled_computer.run_compiled(compiled);
+    assert_eq!(compiled_result, interpreted_result);
+    assert_eq!(compiled_computer.memory(), interpreted.memory());
+    assert_eq!(compiled_computer.position(), interpreted.position());
+    assert_eq!(compiled_computer.output, interpreted.output);
+    assert_eq!(compiled_computer.state, interpreted.state);
+}
+
+fn compiled_fixtures_match_interpreter() {
+    for &(raw_code, compiled) in FIXTURES {
+        for input in [
+            vec![],
+            vec![0],
+            vec![1],
+            vec![5],
+            vec![7],
+            vec![8],
+            vec![9],
+            vec![-3, 0],
+            vec![3, 17],
+            vec![9, 0],
+        ]
+        .iter()
+        {
+            let computer = IntcodeComputer::new(raw_code, input.clone());
+            assert_same_as_interpreter(&computer, compiled);
+        }
+    }
+}
+
+fn compiled_fixtures_resume_after_waiting() {
+    for &(raw_code, compiled) in FIXTURES {
+        let mut interpreted = IntcodeComputer::new(raw_code, vec![]);
+        let mut compiled_computer = interpreted.clone();
+        interpreted.run().unwrap();
+        compiled_computer.run_compiled(compiled).unwrap();
+        for input in 5..8 {
+            if interpreted.state != State::WaitingForInput {
+                break;
+            }
+            interpreted.feed_input(input).unwrap();
+            compiled_computer.feed_input(input).unwrap();
+            assert_eq!(compiled_computer.output, interpreted.output);
+            assert_eq!(compiled_computer.state, interpreted.state);
+        }
+    }
+}
+
+fn day7_amplifier_runs_without_interpreter() {
+    let &(_, compiled) = FIXTURES.last().unwrap();
+    let mut memory = IntcodeComputer::new(&read_file("../day7/input"), vec![])
+        .memory()
+        .to_vec();
+    let mut input: VecDeque<i32> = vec![0, 3].into_iter().collect();
+    let mut output = Vec::new();
+    match compiled(&mut memory, 0, &mut Io::new(&mut input, &mut output)) {
+        Exit::Halted(_) => {}
+        _ => panic!("compiled amplifier did not run to completion"),
+    }
+    assert_eq!(output.len(), 1);
+}
+
+fn patched_operands_stay_compiled() {
+    let day2 = FIXTURES
+        .iter()
+        .find(|(raw_code, _)| raw_code.starts_with("1,0,0,3,1,1,2,3"))
+        .unwrap();
+    for noun in 0..20 {
+        for verb in 0..20 {
+            let mut computer = IntcodeComputer::new(day2.0, Vec::new());
+            computer.set_memory(1, noun).unwrap();
+            computer.set_memory(2, verb).unwrap();
+            assert_same_as_interpreter(&computer, day2.1);
+        }
+    }
+}
+
+fn self_modifying_code_falls_back() {
+    // Overwrites the add at 4 with a halt before reaching it.
+    let raw_code = "1101,0,99,4,1,0,0,0,104,1,99";
+    let compiled = FIXTURES
+        .iter()
+        .find(|(code, _)| *code == raw_code)
+        .unwrap()
+        .1;
+    let computer = IntcodeComputer::new(raw_code, Vec::new());
+    assert_same_as_interpreter(&computer, compiled);
+}
+
+fn main() {
+    compiled_fixtures_match_interpreter();
+    compiled_fixtures_resume_after_waiting();
+    day7_amplifier_runs_without_interpreter();
+    patched_operands_stay_compiled();
+    self_modifying_code_falls_back();
+}